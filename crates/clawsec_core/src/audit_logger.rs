@@ -1,11 +1,14 @@
 use std::fs::{File, OpenOptions};
-use std::io::{self, Write, BufWriter};
+use std::io::{self, BufReader, Read, Write, BufWriter};
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use sha2::{Digest, Sha256};
+
 const LOG_MAGIC_HEADER: &[u8] = b"CLAWSEC_AUDIT_V1";
 const MAX_LOG_SIZE_BYTES: u64 = 10 * 1024 * 1024; // 10MB
+const HASH_LEN: usize = 32;
 
 #[derive(Debug, Clone)]
 pub enum AuditSeverity {
@@ -16,6 +19,19 @@ pub enum AuditSeverity {
     IntegrityViolation,
 }
 
+impl AuditSeverity {
+    fn code(&self) -> u8 {
+        match self {
+            AuditSeverity::Info => 0,
+            AuditSeverity::AccessGranted => 1,
+            AuditSeverity::AccessDenied => 2,
+            AuditSeverity::SystemChange => 3,
+            AuditSeverity::IntegrityViolation => 4,
+        }
+    }
+
+}
+
 #[derive(Debug)]
 pub struct AuditEntry {
     timestamp: u64,
@@ -34,26 +50,48 @@ impl AuditEntry {
             actor_id: actor.to_string(),
             action: action.to_string(),
             resource: resource.to_string(),
-            checksum: String::new(), // Calculated later
+            checksum: String::new(), // Filled in by AuditLogger::log when the entry is chained.
         }
     }
 
-    pub fn serialize(&self) -> Vec<u8> {
+    /// Fixed byte layout hashed into the chain: big-endian timestamp, a
+    /// one-byte severity code, then the three identifying fields each
+    /// null-terminated. This exact layout, including the null terminators,
+    /// is part of the chain's integrity contract — changing it breaks
+    /// verification of every log written before the change.
+    fn serialize_fields(&self) -> Vec<u8> {
         let mut buffer = Vec::new();
         buffer.extend_from_slice(&self.timestamp.to_be_bytes());
+        buffer.push(self.severity.code());
         buffer.extend_from_slice(self.actor_id.as_bytes());
-        buffer.push(0); // Null terminator
+        buffer.push(0);
         buffer.extend_from_slice(self.action.as_bytes());
         buffer.push(0);
+        buffer.extend_from_slice(self.resource.as_bytes());
+        buffer.push(0);
         buffer
     }
 }
 
+fn read_null_terminated(reader: &mut impl Read) -> io::Result<String> {
+    let mut bytes = Vec::new();
+    let mut byte = [0u8; 1];
+    loop {
+        reader.read_exact(&mut byte)?;
+        if byte[0] == 0 {
+            break;
+        }
+        bytes.push(byte[0]);
+    }
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
 pub struct AuditLogger {
     base_path: PathBuf,
     current_file: Arc<Mutex<BufWriter<File>>>,
     current_size: Arc<Mutex<u64>>,
     retention_days: u32,
+    prev_hash: Arc<Mutex<[u8; HASH_LEN]>>,
 }
 
 impl AuditLogger {
@@ -63,6 +101,8 @@ impl AuditLogger {
         }
 
         let log_file_path = path.join("current.audit");
+        let prev_hash = Self::last_checksum(&log_file_path)?.unwrap_or_else(Self::genesis_hash);
+
         let file = OpenOptions::new()
             .create(true)
             .append(true)
@@ -75,9 +115,36 @@ impl AuditLogger {
             current_file: Arc::new(Mutex::new(BufWriter::new(file))),
             current_size: Arc::new(Mutex::new(size)),
             retention_days: retention,
+            prev_hash: Arc::new(Mutex::new(prev_hash)),
         })
     }
 
+    /// The hash chain's root: the magic header folded in as the genesis
+    /// link, so an empty log still has a well-defined starting hash.
+    pub fn genesis_hash() -> [u8; HASH_LEN] {
+        Sha256::digest(LOG_MAGIC_HEADER).into()
+    }
+
+    /// Recovers the running `prev_hash` after a restart by reading the
+    /// last record already on disk, if any.
+    fn last_checksum(path: &Path) -> io::Result<Option<[u8; HASH_LEN]>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut last = None;
+        loop {
+            match read_record(&mut reader) {
+                Ok(Some((checksum, _))) => last = Some(checksum),
+                Ok(None) => break,
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(last)
+    }
+
     pub fn log(&self, mut entry: AuditEntry) -> io::Result<()> {
         let mut writer = self.current_file.lock().unwrap();
         let mut size = self.current_size.lock().unwrap();
@@ -87,14 +154,23 @@ impl AuditLogger {
             writer.flush()?;
         }
 
-        // Simulate cryptographic checksumming of the log entry for immutability
-        entry.checksum = format!("{:x}", md5::compute(entry.serialize())); // Mock MD5
+        let mut prev_hash = self.prev_hash.lock().unwrap();
+        let fields = entry.serialize_fields();
 
-        let data = entry.serialize();
-        writer.write_all(&data)?;
-        writer.write_all(b"\n")?;
-        
-        *size += data.len() as u64 + 1;
+        let mut preimage = Vec::with_capacity(HASH_LEN + fields.len());
+        preimage.extend_from_slice(&*prev_hash);
+        preimage.extend_from_slice(&fields);
+        let hash: [u8; HASH_LEN] = Sha256::digest(&preimage).into();
+        entry.checksum = hex::encode(hash);
+
+        let mut record = Vec::with_capacity(HASH_LEN + fields.len());
+        record.extend_from_slice(&hash);
+        record.extend_from_slice(&fields);
+        writer.write_all(&record)?;
+        writer.flush()?;
+
+        *size += record.len() as u64;
+        *prev_hash = hash;
 
         Ok(())
     }
@@ -102,38 +178,101 @@ impl AuditLogger {
     pub fn rotate_log(&self) -> io::Result<()> {
         let mut writer = self.current_file.lock().unwrap();
         writer.flush()?;
-        
+
         let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
         let new_name = self.base_path.join(format!("audit_{}.archive", timestamp));
         let current_path = self.base_path.join("current.audit");
 
         std::fs::rename(&current_path, &new_name)?;
-        
+
         let new_file = OpenOptions::new()
             .create(true)
             .write(true)
             .open(&current_path)?;
-            
+
         *writer = BufWriter::new(new_file);
         let mut size = self.current_size.lock().unwrap();
         *size = 0;
 
+        // `prev_hash` is deliberately left untouched: the next entry
+        // written to the fresh file chains onto the last hash of the
+        // rotated-out file, so the archive set verifies end-to-end.
         Ok(())
     }
 
-    pub fn verify_chain(&self, file_path: &Path) -> bool {
-        // This would read the file and verify the hash chain of entries
-        true
+    /// Verifies `file_path` as the first file in a chain (its first entry
+    /// must link back to [`genesis_hash`]).
+    pub fn verify_chain(&self, file_path: &Path) -> Result<(), u64> {
+        self.verify_chain_from(file_path, Self::genesis_hash())
     }
-}
 
-// Mock MD5 module since we don't want external deps for this snippet
-mod md5 {
-    pub fn compute(data: Vec<u8>) -> u64 {
-        let mut hash = 0u64;
-        for byte in data {
-            hash = hash.wrapping_add(byte as u64);
+    /// Verifies `file_path` assuming `expected_start` is the hash its
+    /// first entry should chain onto — the genesis hash for the very
+    /// first file in an archive set, or the previous file's final hash
+    /// for any later one. Re-reads front to back, recomputing each link;
+    /// returns the byte offset of the first record that doesn't match
+    /// (tampering) or doesn't fully parse (truncation).
+    pub fn verify_chain_from(&self, file_path: &Path, expected_start: [u8; HASH_LEN]) -> Result<(), u64> {
+        let mut reader = BufReader::new(match File::open(file_path) {
+            Ok(f) => f,
+            Err(_) => return Err(0),
+        });
+
+        let mut prev_hash = expected_start;
+        let mut offset: u64 = 0;
+
+        loop {
+            let record_start = offset;
+            match read_record(&mut reader) {
+                Ok(Some((stored_hash, fields))) => {
+                    let mut preimage = Vec::with_capacity(HASH_LEN + fields.len());
+                    preimage.extend_from_slice(&prev_hash);
+                    preimage.extend_from_slice(&fields);
+                    let recomputed: [u8; HASH_LEN] = Sha256::digest(&preimage).into();
+
+                    if recomputed != stored_hash {
+                        return Err(record_start);
+                    }
+
+                    offset += (HASH_LEN + fields.len()) as u64;
+                    prev_hash = recomputed;
+                }
+                Ok(None) => return Ok(()),
+                Err(_) => return Err(record_start),
+            }
         }
-        hash
     }
+
+    /// The final hash of `file_path`, for chaining into the next archive
+    /// file's expected starting hash during verification.
+    pub fn final_hash(&self, file_path: &Path) -> io::Result<[u8; HASH_LEN]> {
+        Self::last_checksum(file_path)?.ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "empty audit file"))
+    }
+}
+
+/// Reads one `(checksum, serialized_fields)` record from `reader`, or
+/// `Ok(None)` at a clean end-of-file between records.
+fn read_record(reader: &mut impl Read) -> io::Result<Option<([u8; HASH_LEN], Vec<u8>)>> {
+    let mut checksum = [0u8; HASH_LEN];
+    if reader.read(&mut checksum[..1])? == 0 {
+        return Ok(None);
+    }
+    reader.read_exact(&mut checksum[1..])?;
+
+    let mut fields = Vec::new();
+    let mut timestamp = [0u8; 8];
+    reader.read_exact(&mut timestamp)?;
+    fields.extend_from_slice(&timestamp);
+
+    let mut severity = [0u8; 1];
+    reader.read_exact(&mut severity)?;
+    fields.push(severity[0]);
+
+    for _ in 0..3 {
+        let field = read_null_terminated(reader)?;
+        fields.extend_from_slice(field.as_bytes());
+        fields.push(0);
+    }
+
+    Ok(Some((checksum, fields)))
 }
@@ -0,0 +1,549 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use sha3::{Digest, Keccak256};
+
+use crate::crypto_vault::HardwareSecurityModule;
+use crate::raft_storage::RaftStorage;
+
+const TICK_INTERVAL: Duration = Duration::from_millis(20);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VotePhase {
+    PreVote,
+    PreCommit,
+}
+
+/// A signed value proposed by the authority whose turn it is at
+/// `(height, round)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Proposal {
+    pub height: u64,
+    pub round: u64,
+    pub value: Vec<u8>,
+    pub proposer_id: String,
+    pub signature: String,
+}
+
+/// A signed pre-vote or pre-commit cast by one authority for a value at
+/// `(height, round)`. `value_hash` is empty for a nil vote (timeout, no
+/// proposal seen, or a proposal that failed validation).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vote {
+    pub height: u64,
+    pub round: u64,
+    pub phase: VotePhase,
+    pub value_hash: Vec<u8>,
+    pub authority_id: String,
+    pub signature: String,
+}
+
+/// Two different proposals signed by the same authority for the same
+/// height/round — proof the authority is equivocating.
+#[derive(Debug, Clone)]
+pub struct EquivocationRecord {
+    pub authority_id: String,
+    pub height: u64,
+    pub round: u64,
+    pub first: Proposal,
+    pub second: Proposal,
+}
+
+fn hash_value(value: &[u8]) -> Vec<u8> {
+    Keccak256::digest(value).to_vec()
+}
+
+/// Votes collected for a single (height, round, phase), keyed by authority
+/// so a later vote from the same authority replaces its earlier one rather
+/// than padding out the quorum count.
+#[derive(Default)]
+struct VoteSet {
+    by_authority: HashMap<String, Vote>,
+}
+
+impl VoteSet {
+    fn record(&mut self, vote: Vote) {
+        self.by_authority.insert(vote.authority_id.clone(), vote);
+    }
+
+    /// Returns the value hash with quorum support, if any.
+    fn quorum_value(&self, authority_count: usize) -> Option<Vec<u8>> {
+        let mut tally: HashMap<&[u8], usize> = HashMap::new();
+        for vote in self.by_authority.values() {
+            if !vote.value_hash.is_empty() {
+                *tally.entry(vote.value_hash.as_slice()).or_insert(0) += 1;
+            }
+        }
+        tally
+            .into_iter()
+            .find(|(_, count)| has_quorum(*count, authority_count))
+            .map(|(hash, _)| hash.to_vec())
+    }
+}
+
+fn has_quorum(count: usize, authority_count: usize) -> bool {
+    count * 3 > authority_count * 2
+}
+
+/// Authority-round BFT consensus (propose -> pre-vote -> pre-commit), an
+/// alternative to `ConsensusEngine`'s Raft path for clusters where peers may
+/// be malicious rather than merely crash-prone. A value only commits once
+/// more than two-thirds of the fixed authority set has pre-committed it, and
+/// an authority that signs two different proposals for the same
+/// height/round is caught and recorded rather than silently tolerated.
+pub struct AuthorityConsensus {
+    node_id: String,
+    authorities: Vec<String>,
+    /// Hex-encoded SEC1 public key for each authority in `authorities`,
+    /// used to verify the signature on every inbound proposal and vote
+    /// before it's recorded. A message from an `authority_id` missing here
+    /// (or anyone not in `authorities` at all) is dropped rather than
+    /// tallied — otherwise any peer could fabricate arbitrary
+    /// `authority_id`s and manufacture a quorum for free.
+    authority_keys: HashMap<String, String>,
+    round_timeout: Duration,
+    hsm: Arc<HardwareSecurityModule>,
+    storage: Arc<dyn RaftStorage>,
+
+    height: RwLock<u64>,
+    round: RwLock<u64>,
+    round_started_at: RwLock<Instant>,
+
+    seen_proposals: RwLock<HashMap<(u64, u64, String), Proposal>>,
+    pre_votes: RwLock<HashMap<(u64, u64), VoteSet>>,
+    pre_commits: RwLock<HashMap<(u64, u64), VoteSet>>,
+    equivocations: RwLock<Vec<EquivocationRecord>>,
+    committed: RwLock<HashMap<u64, Vec<u8>>>,
+}
+
+impl AuthorityConsensus {
+    pub fn new(
+        node_id: String,
+        authorities: Vec<String>,
+        authority_keys: HashMap<String, String>,
+        round_timeout: Duration,
+        hsm: Arc<HardwareSecurityModule>,
+        storage: Arc<dyn RaftStorage>,
+    ) -> Self {
+        AuthorityConsensus {
+            node_id,
+            authorities,
+            authority_keys,
+            round_timeout,
+            hsm,
+            storage,
+            height: RwLock::new(1),
+            round: RwLock::new(0),
+            round_started_at: RwLock::new(Instant::now()),
+            seen_proposals: RwLock::new(HashMap::new()),
+            pre_votes: RwLock::new(HashMap::new()),
+            pre_commits: RwLock::new(HashMap::new()),
+            equivocations: RwLock::new(Vec::new()),
+            committed: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Round-robin proposer selection: every authority gets a turn, and a
+    /// round timeout rotates to the next one without requiring a vote.
+    pub fn proposer_for(&self, height: u64, round: u64) -> &str {
+        let index = ((height.saturating_sub(1) + round) as usize) % self.authorities.len();
+        &self.authorities[index]
+    }
+
+    pub fn current_height(&self) -> u64 {
+        *self.height.read().unwrap()
+    }
+
+    pub fn current_round(&self) -> u64 {
+        *self.round.read().unwrap()
+    }
+
+    /// Spawns the background thread that rotates the proposer once a round
+    /// runs past `round_timeout` without reaching a pre-commit quorum.
+    pub fn start(self: &Arc<Self>) {
+        let engine = Arc::clone(self);
+        thread::spawn(move || loop {
+            thread::sleep(TICK_INTERVAL);
+            engine.check_round_timeout();
+        });
+    }
+
+    fn check_round_timeout(&self) {
+        let elapsed = self.round_started_at.read().unwrap().elapsed();
+        if elapsed >= self.round_timeout {
+            *self.round.write().unwrap() += 1;
+            *self.round_started_at.write().unwrap() = Instant::now();
+        }
+    }
+
+    /// Signs and returns a new proposal for the current height/round, if
+    /// this node is the proposer whose turn it is.
+    pub fn propose(&self, value: Vec<u8>) -> Option<Proposal> {
+        let height = self.current_height();
+        let round = self.current_round();
+        if self.proposer_for(height, round) != self.node_id {
+            return None;
+        }
+
+        let signature = self.sign(&value, height, round);
+        let proposal = Proposal {
+            height,
+            round,
+            value,
+            proposer_id: self.node_id.clone(),
+            signature,
+        };
+        self.record_proposal(proposal.clone());
+        Some(proposal)
+    }
+
+    fn proposal_payload(value: &[u8], height: u64, round: u64) -> Vec<u8> {
+        let mut payload = Vec::with_capacity(value.len() + 16);
+        payload.extend_from_slice(&height.to_be_bytes());
+        payload.extend_from_slice(&round.to_be_bytes());
+        payload.extend_from_slice(value);
+        payload
+    }
+
+    fn sign(&self, value: &[u8], height: u64, round: u64) -> String {
+        self.hsm.sign_transaction(&Self::proposal_payload(value, height, round)).unwrap_or_default()
+    }
+
+    /// Verifies `signature` was produced by `authority_id`'s registered
+    /// key over `payload`. An authority absent from `authorities` or
+    /// `authority_keys` never passes — there is no such thing as an
+    /// unverifiable-but-trusted authority.
+    fn verify_authority_signature(&self, authority_id: &str, payload: &[u8], signature: &str) -> bool {
+        if !self.authorities.iter().any(|a| a == authority_id) {
+            return false;
+        }
+        let Some(pubkey) = self.authority_keys.get(authority_id) else {
+            return false;
+        };
+        self.hsm.verify(payload, signature, pubkey).unwrap_or(false)
+    }
+
+    /// Accepts an inbound proposal from a peer authority. Returns the
+    /// equivocation record if the proposer had already signed a different
+    /// value for the same height/round; the second proposal is rejected in
+    /// that case and the first one stands. A proposal from an unknown
+    /// authority, or with a signature that doesn't verify, is silently
+    /// dropped rather than recorded.
+    pub fn receive_proposal(&self, proposal: Proposal) -> Result<(), EquivocationRecord> {
+        let payload = Self::proposal_payload(&proposal.value, proposal.height, proposal.round);
+        if !self.verify_authority_signature(&proposal.proposer_id, &payload, &proposal.signature) {
+            return Ok(());
+        }
+
+        let key = (proposal.height, proposal.round, proposal.proposer_id.clone());
+        let mut seen = self.seen_proposals.write().unwrap();
+
+        if let Some(existing) = seen.get(&key) {
+            if existing.value != proposal.value {
+                let record = EquivocationRecord {
+                    authority_id: proposal.proposer_id.clone(),
+                    height: proposal.height,
+                    round: proposal.round,
+                    first: existing.clone(),
+                    second: proposal,
+                };
+                self.equivocations.write().unwrap().push(record.clone());
+                return Err(record);
+            }
+            return Ok(());
+        }
+
+        seen.insert(key, proposal.clone());
+        drop(seen);
+        self.record_proposal(proposal);
+        Ok(())
+    }
+
+    fn record_proposal(&self, proposal: Proposal) {
+        let key = (proposal.height, proposal.round, proposal.proposer_id.clone());
+        self.seen_proposals.write().unwrap().entry(key).or_insert(proposal);
+    }
+
+    /// Casts this node's pre-vote for the proposal it has seen at
+    /// `(height, round)`, or a nil vote if none has arrived yet.
+    pub fn pre_vote(&self, height: u64, round: u64) -> Vote {
+        let proposer = self.proposer_for(height, round).to_string();
+        let value_hash = self
+            .seen_proposals
+            .read()
+            .unwrap()
+            .get(&(height, round, proposer))
+            .map(|p| hash_value(&p.value))
+            .unwrap_or_default();
+
+        self.cast_vote(height, round, VotePhase::PreVote, value_hash)
+    }
+
+    /// A vote's signed payload includes `phase` so a PreVote signature
+    /// can't be replayed byte-for-byte as a PreCommit for the same
+    /// height/round and value hash.
+    fn vote_payload(height: u64, round: u64, phase: VotePhase, value_hash: &[u8]) -> Vec<u8> {
+        let mut payload = value_hash.to_vec();
+        payload.push(match phase {
+            VotePhase::PreVote => 0,
+            VotePhase::PreCommit => 1,
+        });
+        payload.extend_from_slice(&height.to_be_bytes());
+        payload.extend_from_slice(&round.to_be_bytes());
+        payload
+    }
+
+    fn cast_vote(&self, height: u64, round: u64, phase: VotePhase, value_hash: Vec<u8>) -> Vote {
+        let payload = Self::vote_payload(height, round, phase, &value_hash);
+        let signature = self.hsm.sign_transaction(&payload).unwrap_or_default();
+
+        let vote = Vote {
+            height,
+            round,
+            phase,
+            value_hash,
+            authority_id: self.node_id.clone(),
+            signature,
+        };
+        self.receive_vote(vote.clone());
+        vote
+    }
+
+    /// Records an inbound vote (including our own) and, once a pre-vote
+    /// quorum forms for a value, automatically casts this node's
+    /// pre-commit for it. A vote from an unknown authority, or whose
+    /// signature doesn't verify, is dropped instead of tallied.
+    pub fn receive_vote(&self, vote: Vote) {
+        let payload = Self::vote_payload(vote.height, vote.round, vote.phase, &vote.value_hash);
+        if !self.verify_authority_signature(&vote.authority_id, &payload, &vote.signature) {
+            return;
+        }
+
+        let key = (vote.height, vote.round);
+        let phase = vote.phase;
+
+        match phase {
+            VotePhase::PreVote => {
+                self.pre_votes.write().unwrap().entry(key).or_default().record(vote.clone());
+                if let Some(value_hash) = self
+                    .pre_votes
+                    .read()
+                    .unwrap()
+                    .get(&key)
+                    .and_then(|set| set.quorum_value(self.authorities.len()))
+                {
+                    self.cast_vote(vote.height, vote.round, VotePhase::PreCommit, value_hash);
+                }
+            }
+            VotePhase::PreCommit => {
+                self.pre_commits.write().unwrap().entry(key).or_default().record(vote.clone());
+                if let Some(value_hash) = self
+                    .pre_commits
+                    .read()
+                    .unwrap()
+                    .get(&key)
+                    .and_then(|set| set.quorum_value(self.authorities.len()))
+                {
+                    self.try_commit(vote.height, vote.round, &value_hash);
+                }
+            }
+        }
+    }
+
+    fn try_commit(&self, height: u64, round: u64, value_hash: &[u8]) {
+        let proposer = self.proposer_for(height, round).to_string();
+        let value = match self.seen_proposals.read().unwrap().get(&(height, round, proposer)) {
+            Some(proposal) if hash_value(&proposal.value) == value_hash => proposal.value.clone(),
+            _ => return,
+        };
+
+        let mut committed = self.committed.write().unwrap();
+        if committed.contains_key(&height) {
+            return;
+        }
+        committed.insert(height, value.clone());
+
+        if let Err(e) = self.storage.append(&[crate::distributed_consensus::LogEntry {
+            term: height,
+            command: value,
+            timestamp: 0,
+        }]) {
+            eprintln!("failed to persist BFT-committed value for height {}: {}", height, e);
+        }
+
+        *self.height.write().unwrap() = height + 1;
+        *self.round.write().unwrap() = 0;
+        *self.round_started_at.write().unwrap() = Instant::now();
+    }
+
+    pub fn committed_value(&self, height: u64) -> Option<Vec<u8>> {
+        self.committed.read().unwrap().get(&height).cloned()
+    }
+
+    pub fn equivocations(&self) -> Vec<EquivocationRecord> {
+        self.equivocations.read().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distributed_consensus::LogEntry;
+    use std::sync::Mutex as StdMutex;
+
+    struct InMemoryStorage {
+        log: StdMutex<Vec<LogEntry>>,
+    }
+
+    impl InMemoryStorage {
+        fn new() -> Self {
+            InMemoryStorage { log: StdMutex::new(Vec::new()) }
+        }
+    }
+
+    impl RaftStorage for InMemoryStorage {
+        fn persist_hard_state(&self, _term: u64, _voted_for: Option<&str>) -> std::io::Result<()> {
+            Ok(())
+        }
+
+        fn load_hard_state(&self) -> std::io::Result<(u64, Option<String>)> {
+            Ok((0, None))
+        }
+
+        fn append(&self, entries: &[LogEntry]) -> std::io::Result<()> {
+            self.log.lock().unwrap().extend_from_slice(entries);
+            Ok(())
+        }
+
+        fn entries(&self, _from: u64, _to: u64) -> std::io::Result<Vec<LogEntry>> {
+            Ok(self.log.lock().unwrap().clone())
+        }
+
+        fn truncate_suffix(&self, _index: u64) -> std::io::Result<()> {
+            Ok(())
+        }
+
+        fn last_index(&self) -> std::io::Result<u64> {
+            Ok(self.log.lock().unwrap().len() as u64)
+        }
+    }
+
+    /// Builds a 4-authority cluster, each with its own signing identity but
+    /// sharing a map of every authority's public key, so votes and
+    /// proposals can be verified across nodes the way real peers would.
+    fn build_cluster(node_ids: &[&str]) -> Vec<AuthorityConsensus> {
+        let authorities: Vec<String> = node_ids.iter().map(|s| s.to_string()).collect();
+        let hsms: Vec<Arc<HardwareSecurityModule>> = node_ids
+            .iter()
+            .map(|_| Arc::new(HardwareSecurityModule::connect().unwrap()))
+            .collect();
+
+        let authority_keys: HashMap<String, String> = authorities
+            .iter()
+            .cloned()
+            .zip(hsms.iter().map(|hsm| hsm.public_key_hex().unwrap()))
+            .collect();
+
+        authorities
+            .iter()
+            .cloned()
+            .zip(hsms)
+            .map(|(node_id, hsm)| {
+                AuthorityConsensus::new(
+                    node_id,
+                    authorities.clone(),
+                    authority_keys.clone(),
+                    Duration::from_secs(10),
+                    hsm,
+                    Arc::new(InMemoryStorage::new()),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn commits_a_value_once_two_thirds_pre_commit() {
+        let nodes = build_cluster(&["a", "b", "c", "d"]);
+        let value = b"block-1".to_vec();
+
+        let proposal = nodes[0].propose(value.clone()).expect("a proposes height 1 round 0");
+        for node in &nodes {
+            node.receive_proposal(proposal.clone()).unwrap();
+        }
+
+        // Every node pre-votes, and broadcasts its pre-vote to every other
+        // node, same as `receive_vote` would see over the wire.
+        let pre_votes: Vec<Vote> = nodes.iter().map(|n| n.pre_vote(1, 0)).collect();
+        for node in &nodes {
+            for vote in &pre_votes {
+                node.receive_vote(vote.clone());
+            }
+        }
+
+        // The pre-vote quorum makes each node cast its own pre-commit;
+        // deliver every node's pre-commit to every other node as well.
+        let pre_commits: Vec<Vote> = nodes
+            .iter()
+            .filter_map(|n| {
+                n.pre_commits
+                    .read()
+                    .unwrap()
+                    .get(&(1, 0))
+                    .and_then(|set| set.by_authority.get(&n.node_id).cloned())
+            })
+            .collect();
+        for node in &nodes {
+            for vote in &pre_commits {
+                node.receive_vote(vote.clone());
+            }
+        }
+
+        for node in &nodes {
+            assert_eq!(node.committed_value(1), Some(value.clone()));
+        }
+    }
+
+    #[test]
+    fn rejects_votes_and_proposals_from_unknown_authorities() {
+        let nodes = build_cluster(&["a", "b", "c", "d"]);
+        let impostor_hsm = HardwareSecurityModule::connect().unwrap();
+
+        let mut forged_proposal = nodes[0].propose(b"legit".to_vec()).unwrap();
+        forged_proposal.proposer_id = "mallory".to_string();
+        forged_proposal.signature = impostor_hsm.sign_transaction(b"whatever").unwrap();
+        assert!(nodes[1].receive_proposal(forged_proposal).is_ok());
+        assert_eq!(nodes[1].committed_value(1), None);
+
+        let forged_vote = Vote {
+            height: 1,
+            round: 0,
+            phase: VotePhase::PreVote,
+            value_hash: hash_value(b"legit"),
+            authority_id: "mallory".to_string(),
+            signature: impostor_hsm.sign_transaction(b"whatever").unwrap(),
+        };
+        nodes[1].receive_vote(forged_vote);
+        assert!(nodes[1].committed_value(1).is_none());
+    }
+
+    #[test]
+    fn detects_equivocation() {
+        let nodes = build_cluster(&["a", "b", "c", "d"]);
+
+        let first = nodes[0].propose(b"value-one".to_vec()).unwrap();
+        nodes[1].receive_proposal(first.clone()).unwrap();
+
+        let mut second = first.clone();
+        second.value = b"value-two".to_vec();
+        second.signature = nodes[0].sign(&second.value, second.height, second.round);
+
+        let result = nodes[1].receive_proposal(second.clone());
+        let record = result.expect_err("a second, differing proposal for the same height/round must be flagged");
+        assert_eq!(record.authority_id, "a");
+        assert_eq!(record.first.value, first.value);
+        assert_eq!(record.second.value, second.value);
+        assert_eq!(nodes[1].equivocations().len(), 1);
+    }
+}
@@ -1,8 +1,22 @@
 use std::collections::HashMap;
+use std::net::TcpListener;
+use std::path::Path;
 use std::sync::{Arc, Mutex, RwLock};
 use std::thread;
 use std::time::{Duration, Instant};
 
+use serde::{Deserialize, Serialize};
+
+use crate::raft_storage::{RaftStorage, RocksRaftStorage};
+use crate::raft_transport::{
+    AppendEntriesRequest, RequestVoteRequest, TcpTransport, Transport,
+};
+
+const DEFAULT_HEARTBEAT_INTERVAL: Duration = Duration::from_millis(50);
+const TICK_INTERVAL: Duration = Duration::from_millis(20);
+const RPC_RETRY_ATTEMPTS: u32 = 2;
+const RPC_RETRY_BACKOFF: Duration = Duration::from_millis(25);
+
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub enum NodeState {
     Follower,
@@ -10,7 +24,7 @@ pub enum NodeState {
     Leader,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LogEntry {
     pub term: u64,
     pub command: Vec<u8>,
@@ -27,6 +41,7 @@ pub struct PeerNode {
 
 pub struct ConsensusEngine {
     node_id: String,
+    listen_address: String,
     current_term: Arc<RwLock<u64>>,
     voted_for: Arc<RwLock<Option<String>>>,
     log: Arc<RwLock<Vec<LogEntry>>>,
@@ -35,12 +50,38 @@ pub struct ConsensusEngine {
     state: Arc<RwLock<NodeState>>,
     peers: Arc<RwLock<HashMap<String, PeerNode>>>,
     election_timeout: Duration,
+    heartbeat_interval: Duration,
+    last_leader_contact: Arc<Mutex<Instant>>,
+    storage: Arc<dyn RaftStorage>,
+    transport: Arc<dyn Transport>,
 }
 
 use std::sync::atomic::{AtomicU64, Ordering};
 
 impl ConsensusEngine {
-    pub fn new(node_id: String, peers_list: Vec<(String, String)>) -> Self {
+    /// Opens (or creates) the on-disk RocksDB store at `data_dir` and recovers
+    /// `current_term`, `voted_for`, and the full log tail from it before the
+    /// engine starts serving RPCs. `commit_index` is not persisted and starts
+    /// at 0 after every restart. Peers are dialed over TCP at the address each
+    /// was registered with.
+    pub fn new(
+        node_id: String,
+        listen_address: String,
+        peers_list: Vec<(String, String)>,
+        data_dir: &Path,
+    ) -> std::io::Result<Self> {
+        let storage: Arc<dyn RaftStorage> = Arc::new(RocksRaftStorage::open(data_dir)?);
+        let transport: Arc<dyn Transport> = Arc::new(TcpTransport::new(RPC_RETRY_ATTEMPTS, RPC_RETRY_BACKOFF));
+        Self::with_storage(node_id, listen_address, peers_list, storage, transport)
+    }
+
+    pub fn with_storage(
+        node_id: String,
+        listen_address: String,
+        peers_list: Vec<(String, String)>,
+        storage: Arc<dyn RaftStorage>,
+        transport: Arc<dyn Transport>,
+    ) -> std::io::Result<Self> {
         let mut peers = HashMap::new();
         for (pid, addr) in peers_list {
             peers.insert(pid.clone(), PeerNode {
@@ -52,53 +93,262 @@ impl ConsensusEngine {
             });
         }
 
-        ConsensusEngine {
+        let (current_term, voted_for) = storage.load_hard_state()?;
+        let last_index = storage.last_index()?;
+        let log = storage.entries(1, last_index)?;
+
+        Ok(ConsensusEngine {
             node_id,
-            current_term: Arc::new(RwLock::new(0)),
-            voted_for: Arc::new(RwLock::new(None)),
-            log: Arc::new(RwLock::new(Vec::new())),
+            listen_address,
+            current_term: Arc::new(RwLock::new(current_term)),
+            voted_for: Arc::new(RwLock::new(voted_for)),
+            log: Arc::new(RwLock::new(log)),
             commit_index: Arc::new(AtomicU64::new(0)),
             last_applied: Arc::new(AtomicU64::new(0)),
             state: Arc::new(RwLock::new(NodeState::Follower)),
             peers: Arc::new(RwLock::new(peers)),
             election_timeout: Duration::from_millis(150 + (rand::random::<u64>() % 150)),
-        }
+            heartbeat_interval: DEFAULT_HEARTBEAT_INTERVAL,
+            last_leader_contact: Arc::new(Mutex::new(Instant::now())),
+            storage,
+            transport,
+        })
     }
 
-    pub fn start(&self) {
-        let state_clone = self.state.clone();
-        let term_clone = self.current_term.clone();
-        
+    /// Binds the RPC listener and spawns the background thread that drives
+    /// the election timeout and, once leader, the heartbeat loop.
+    pub fn start(self: &Arc<Self>) -> std::io::Result<()> {
+        let listener = TcpListener::bind(&self.listen_address)?;
+        let server_engine = Arc::clone(self);
         thread::spawn(move || {
-            loop {
-                thread::sleep(Duration::from_millis(50));
-                // Main event loop would go here
+            let handler_engine = Arc::clone(&server_engine);
+            let result = crate::raft_transport::serve(listener, move |rpc| handler_engine.dispatch_rpc(rpc));
+            if let Err(e) = result {
+                eprintln!("raft rpc server exited: {}", e);
             }
         });
+
+        let engine = Arc::clone(self);
+        let mut last_heartbeat_sent = Instant::now() - engine.heartbeat_interval;
+        thread::spawn(move || loop {
+            thread::sleep(TICK_INTERVAL);
+
+            let state = *engine.state.read().unwrap();
+            match state {
+                NodeState::Leader => {
+                    if last_heartbeat_sent.elapsed() >= engine.heartbeat_interval {
+                        engine.replicate_log();
+                        last_heartbeat_sent = Instant::now();
+                    }
+                }
+                NodeState::Follower | NodeState::Candidate => {
+                    let since_contact = engine.last_leader_contact.lock().unwrap().elapsed();
+                    if since_contact >= engine.election_timeout {
+                        engine.start_election();
+                    }
+                }
+            }
+        });
+
+        Ok(())
     }
 
-    pub fn append_entries(&self, term: u64, leader_id: String, entries: Vec<LogEntry>) -> bool {
+    fn dispatch_rpc(&self, rpc: crate::raft_transport::RaftRpc) -> crate::raft_transport::RaftRpcResponse {
+        use crate::raft_transport::{RaftRpc, RaftRpcResponse};
+        match rpc {
+            RaftRpc::AppendEntries(req) => {
+                let term = *self.current_term.read().unwrap();
+                let success = self.append_entries(
+                    req.term,
+                    req.leader_id,
+                    req.prev_log_index,
+                    req.prev_log_term,
+                    req.entries,
+                    req.leader_commit,
+                );
+                let match_index = self.log.read().unwrap().len() as u64;
+                RaftRpcResponse::AppendEntries(crate::raft_transport::AppendEntriesResponse {
+                    term: term.max(*self.current_term.read().unwrap()),
+                    success,
+                    match_index,
+                })
+            }
+            RaftRpc::RequestVote(req) => {
+                let vote_granted = self.request_vote(
+                    req.term,
+                    req.candidate_id,
+                    req.last_log_index,
+                    req.last_log_term,
+                );
+                RaftRpcResponse::RequestVote(crate::raft_transport::RequestVoteResponse {
+                    term: *self.current_term.read().unwrap(),
+                    vote_granted,
+                })
+            }
+        }
+    }
+
+    fn start_election(&self) {
+        let mut current_term = self.current_term.write().unwrap();
+        *current_term += 1;
+        let election_term = *current_term;
+        *self.state.write().unwrap() = NodeState::Candidate;
+        *self.voted_for.write().unwrap() = Some(self.node_id.clone());
+        self.persist_hard_state(election_term, Some(self.node_id.as_str()));
+        drop(current_term);
+        *self.last_leader_contact.lock().unwrap() = Instant::now();
+
+        let log = self.log.read().unwrap();
+        let last_log_index = log.len() as u64;
+        let last_log_term = log.last().map(|e| e.term).unwrap_or(0);
+        drop(log);
+        let peers: Vec<(String, String)> = self
+            .peers
+            .read()
+            .unwrap()
+            .values()
+            .map(|p| (p.id.clone(), p.address.clone()))
+            .collect();
+
+        let mut votes = 1; // vote for self
+        for (_, address) in &peers {
+            let req = RequestVoteRequest {
+                term: election_term,
+                candidate_id: self.node_id.clone(),
+                last_log_index,
+                last_log_term,
+            };
+            match self.transport.send_request_vote(address, req) {
+                Ok(resp) => {
+                    if resp.term > election_term {
+                        *self.current_term.write().unwrap() = resp.term;
+                        *self.state.write().unwrap() = NodeState::Follower;
+                        *self.voted_for.write().unwrap() = None;
+                        self.persist_hard_state(resp.term, None);
+                        return;
+                    }
+                    if resp.vote_granted {
+                        votes += 1;
+                    }
+                }
+                Err(e) => eprintln!("request_vote to {} failed: {}", address, e),
+            }
+        }
+
+        let majority = (peers.len() + 1) / 2 + 1;
+        if votes >= majority && *self.state.read().unwrap() == NodeState::Candidate {
+            *self.state.write().unwrap() = NodeState::Leader;
+            let next_index = last_log_index + 1;
+            let mut peer_table = self.peers.write().unwrap();
+            for peer in peer_table.values_mut() {
+                peer.next_index = next_index;
+                peer.match_index = 0;
+            }
+        }
+    }
+
+    /// Implements the Raft log-matching property: the follower rejects the
+    /// call unless it already has an entry at `prev_log_index` whose term is
+    /// `prev_log_term`. On a match, any locally-held entry that conflicts
+    /// with an incoming one (same index, different term) truncates the log
+    /// from that point on before the remainder is appended; entries that
+    /// already match are left untouched.
+    pub fn append_entries(
+        &self,
+        term: u64,
+        leader_id: String,
+        prev_log_index: u64,
+        prev_log_term: u64,
+        entries: Vec<LogEntry>,
+        leader_commit: u64,
+    ) -> bool {
         let mut current_term = self.current_term.write().unwrap();
         if term < *current_term {
             return false;
         }
 
+        *self.last_leader_contact.lock().unwrap() = Instant::now();
+        *self.state.write().unwrap() = NodeState::Follower;
+
         if term > *current_term {
             *current_term = term;
-            *self.state.write().unwrap() = NodeState::Follower;
             *self.voted_for.write().unwrap() = None;
+            self.persist_hard_state(*current_term, self.voted_for.read().unwrap().as_deref());
         }
+        drop(current_term);
 
         let mut log = self.log.write().unwrap();
-        log.extend(entries);
-        
-        // Update commit index
-        self.commit_index.store(log.len() as u64, Ordering::SeqCst);
-        
+
+        if prev_log_index > 0 {
+            match log.get((prev_log_index - 1) as usize) {
+                Some(existing) if existing.term == prev_log_term => {}
+                _ => return false,
+            }
+        }
+
+        let mut insert_at = prev_log_index;
+        for (offset, entry) in entries.into_iter().enumerate() {
+            let index = prev_log_index + offset as u64;
+            match log.get(index as usize) {
+                Some(existing) if existing.term == entry.term => {
+                    // Already present and matching; nothing to do.
+                }
+                Some(_) => {
+                    if let Err(e) = self.storage.truncate_suffix(index + 1) {
+                        eprintln!("failed to truncate conflicting log suffix from {}: {}", leader_id, e);
+                        return false;
+                    }
+                    log.truncate(index as usize);
+                    if let Err(e) = self.storage.append(std::slice::from_ref(&entry)) {
+                        eprintln!("failed to persist log entry from {}: {}", leader_id, e);
+                        return false;
+                    }
+                    log.push(entry);
+                }
+                None => {
+                    if let Err(e) = self.storage.append(std::slice::from_ref(&entry)) {
+                        eprintln!("failed to persist log entry from {}: {}", leader_id, e);
+                        return false;
+                    }
+                    log.push(entry);
+                }
+            }
+            insert_at = index + 1;
+        }
+
+        let index_of_last_new_entry = insert_at;
+        if leader_commit > self.commit_index.load(Ordering::SeqCst) {
+            let new_commit_index = leader_commit.min(index_of_last_new_entry);
+            self.commit_index.store(new_commit_index, Ordering::SeqCst);
+
+            let mut last_applied = self.last_applied.load(Ordering::SeqCst);
+            while last_applied < new_commit_index {
+                last_applied += 1;
+                if let Some(committed) = log.get((last_applied - 1) as usize) {
+                    self.apply_command(&committed.command);
+                }
+            }
+            self.last_applied.store(last_applied, Ordering::SeqCst);
+        }
+
         true
     }
 
-    pub fn request_vote(&self, term: u64, candidate_id: String, last_log_idx: u64) -> bool {
+    /// Applies a committed log entry to the node's state machine. The
+    /// consensus layer only guarantees ordering and durability here; the
+    /// actual command interpretation belongs to whatever subsystem is
+    /// replicated (vault operations, BFT votes, etc).
+    fn apply_command(&self, _command: &[u8]) {
+        // State machine application would go here.
+    }
+
+    pub fn request_vote(
+        &self,
+        term: u64,
+        candidate_id: String,
+        last_log_idx: u64,
+        last_log_term: u64,
+    ) -> bool {
         let mut current_term = self.current_term.write().unwrap();
         let mut voted_for = self.voted_for.write().unwrap();
 
@@ -106,30 +356,134 @@ impl ConsensusEngine {
             *current_term = term;
             *self.state.write().unwrap() = NodeState::Follower;
             *voted_for = None;
+            self.persist_hard_state(*current_term, voted_for.as_deref());
         }
 
         if term < *current_term {
             return false;
         }
 
-        if (voted_for.is_none() || voted_for.as_ref() == Some(&candidate_id)) {
-            *voted_for = Some(candidate_id);
-            return true;
+        if voted_for.is_some() && voted_for.as_ref() != Some(&candidate_id) {
+            return false;
+        }
+
+        if !self.candidate_log_is_up_to_date(last_log_term, last_log_idx) {
+            return false;
         }
 
-        false
+        *voted_for = Some(candidate_id);
+        self.persist_hard_state(*current_term, voted_for.as_deref());
+        true
+    }
+
+    /// Raft's election-safety check (§5.4.1): a candidate's log must be at
+    /// least as up-to-date as ours before we grant it our vote, or a
+    /// candidate missing committed entries could win an election. Logs are
+    /// compared first by the term of their last entry, then by length.
+    fn candidate_log_is_up_to_date(&self, candidate_last_term: u64, candidate_last_idx: u64) -> bool {
+        let log = self.log.read().unwrap();
+        let our_last_term = log.last().map(|e| e.term).unwrap_or(0);
+        let our_last_idx = log.len() as u64;
+
+        candidate_last_term > our_last_term
+            || (candidate_last_term == our_last_term && candidate_last_idx >= our_last_idx)
+    }
+
+    /// Fsyncs term/vote to disk before the caller returns an RPC reply, so a
+    /// crash right after replying can never cause a re-vote in an old term.
+    fn persist_hard_state(&self, term: u64, voted_for: Option<&str>) {
+        if let Err(e) = self.storage.persist_hard_state(term, voted_for) {
+            eprintln!("failed to persist hard state: {}", e);
+        }
     }
 
     pub fn replicate_log(&self) {
-        let state = self.state.read().unwrap();
-        if *state != NodeState::Leader {
-            return;
+        {
+            let state = self.state.read().unwrap();
+            if *state != NodeState::Leader {
+                return;
+            }
+        }
+
+        let current_term = *self.current_term.read().unwrap();
+        let leader_commit = self.commit_index.load(Ordering::SeqCst);
+        let log = self.log.read().unwrap().clone();
+        let peer_snapshots: Vec<(String, String, u64)> = self
+            .peers
+            .read()
+            .unwrap()
+            .values()
+            .map(|p| (p.id.clone(), p.address.clone(), p.next_index))
+            .collect();
+
+        for (peer_id, address, next_index) in peer_snapshots {
+            let prev_log_index = next_index.saturating_sub(1);
+            let prev_log_term = if prev_log_index > 0 {
+                log.get((prev_log_index - 1) as usize).map(|e| e.term).unwrap_or(0)
+            } else {
+                0
+            };
+            let entries = log.get(prev_log_index as usize..).map(|s| s.to_vec()).unwrap_or_default();
+
+            let req = AppendEntriesRequest {
+                term: current_term,
+                leader_id: self.node_id.clone(),
+                prev_log_index,
+                prev_log_term,
+                entries,
+                leader_commit,
+            };
+
+            match self.transport.send_append_entries(&address, req) {
+                Ok(resp) => {
+                    if resp.term > current_term {
+                        *self.current_term.write().unwrap() = resp.term;
+                        *self.state.write().unwrap() = NodeState::Follower;
+                        *self.voted_for.write().unwrap() = None;
+                        self.persist_hard_state(resp.term, None);
+                        return;
+                    }
+
+                    let mut peers = self.peers.write().unwrap();
+                    if let Some(peer) = peers.get_mut(&peer_id) {
+                        if resp.success {
+                            peer.match_index = resp.match_index;
+                            peer.next_index = resp.match_index + 1;
+                        } else if peer.next_index > 1 {
+                            peer.next_index -= 1;
+                        }
+                        peer.last_heartbeat = Instant::now();
+                    }
+                }
+                Err(e) => eprintln!("append_entries to {} failed: {}", address, e),
+            }
         }
 
+        self.advance_commit_index(current_term, log.len() as u64);
+    }
+
+    /// Advances `commit_index` to the highest index replicated to a
+    /// majority of peers (counting this leader itself), but only for
+    /// entries whose term matches the leader's current term.
+    fn advance_commit_index(&self, current_term: u64, own_match_index: u64) {
+        let log = self.log.read().unwrap();
         let peers = self.peers.read().unwrap();
-        for peer in peers.values() {
-            // Logic to send AppendEntries RPC to peer
-            // simulate_network_call(peer.address, ...);
+        let total_nodes = peers.len() + 1;
+        let majority = total_nodes / 2 + 1;
+
+        let mut match_indices: Vec<u64> = peers.values().map(|p| p.match_index).collect();
+        match_indices.push(own_match_index);
+        match_indices.sort_unstable_by(|a, b| b.cmp(a));
+
+        let candidate_index = match_indices[majority - 1];
+        if candidate_index == 0 || candidate_index as usize > log.len() {
+            return;
+        }
+
+        if log[(candidate_index - 1) as usize].term == current_term
+            && candidate_index > self.commit_index.load(Ordering::SeqCst)
+        {
+            self.commit_index.store(candidate_index, Ordering::SeqCst);
         }
     }
 
@@ -137,8 +491,144 @@ impl ConsensusEngine {
         let state = self.state.read().unwrap();
         let term = self.current_term.read().unwrap();
         let log_len = self.log.read().unwrap().len();
-        
-        format!("Node: {} | State: {:?} | Term: {} | LogSize: {}", 
+
+        format!("Node: {} | State: {:?} | Term: {} | LogSize: {}",
             self.node_id, *state, *term, log_len)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raft_transport::{AppendEntriesResponse, RequestVoteResponse};
+    use std::sync::Mutex as StdMutex;
+
+    struct InMemoryStorage {
+        hard_state: StdMutex<(u64, Option<String>)>,
+        log: StdMutex<Vec<LogEntry>>,
+    }
+
+    impl InMemoryStorage {
+        fn new() -> Self {
+            InMemoryStorage {
+                hard_state: StdMutex::new((0, None)),
+                log: StdMutex::new(Vec::new()),
+            }
+        }
+    }
+
+    impl RaftStorage for InMemoryStorage {
+        fn persist_hard_state(&self, term: u64, voted_for: Option<&str>) -> std::io::Result<()> {
+            *self.hard_state.lock().unwrap() = (term, voted_for.map(str::to_string));
+            Ok(())
+        }
+
+        fn load_hard_state(&self) -> std::io::Result<(u64, Option<String>)> {
+            Ok(self.hard_state.lock().unwrap().clone())
+        }
+
+        fn append(&self, entries: &[LogEntry]) -> std::io::Result<()> {
+            self.log.lock().unwrap().extend_from_slice(entries);
+            Ok(())
+        }
+
+        fn entries(&self, from: u64, to: u64) -> std::io::Result<Vec<LogEntry>> {
+            if from == 0 || to < from {
+                return Ok(Vec::new());
+            }
+            let log = self.log.lock().unwrap();
+            Ok(log
+                .get((from - 1) as usize..(to as usize).min(log.len()))
+                .map(|s| s.to_vec())
+                .unwrap_or_default())
+        }
+
+        fn truncate_suffix(&self, index: u64) -> std::io::Result<()> {
+            let mut log = self.log.lock().unwrap();
+            log.truncate(index.saturating_sub(1) as usize);
+            Ok(())
+        }
+
+        fn last_index(&self) -> std::io::Result<u64> {
+            Ok(self.log.lock().unwrap().len() as u64)
+        }
+    }
+
+    struct NoopTransport;
+
+    impl Transport for NoopTransport {
+        fn send_append_entries(
+            &self,
+            _address: &str,
+            _req: AppendEntriesRequest,
+        ) -> std::io::Result<AppendEntriesResponse> {
+            Err(std::io::Error::new(std::io::ErrorKind::Other, "not wired up in tests"))
+        }
+
+        fn send_request_vote(
+            &self,
+            _address: &str,
+            _req: RequestVoteRequest,
+        ) -> std::io::Result<RequestVoteResponse> {
+            Err(std::io::Error::new(std::io::ErrorKind::Other, "not wired up in tests"))
+        }
+    }
+
+    fn make_engine() -> ConsensusEngine {
+        ConsensusEngine::with_storage(
+            "node-a".to_string(),
+            "127.0.0.1:0".to_string(),
+            Vec::new(),
+            Arc::new(InMemoryStorage::new()),
+            Arc::new(NoopTransport),
+        )
+        .unwrap()
+    }
+
+    fn entry(term: u64) -> LogEntry {
+        LogEntry {
+            term,
+            command: vec![1],
+            timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn rejects_stale_leader_term() {
+        let engine = make_engine();
+        engine.append_entries(5, "leader".to_string(), 0, 0, vec![entry(5)], 1);
+
+        let accepted = engine.append_entries(3, "old-leader".to_string(), 1, 5, vec![entry(3)], 1);
+
+        assert!(!accepted);
+        assert_eq!(*engine.current_term.read().unwrap(), 5);
+        assert_eq!(engine.log.read().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn rejects_missing_prev_log_entry() {
+        let engine = make_engine();
+        let accepted = engine.append_entries(1, "leader".to_string(), 3, 1, vec![entry(1)], 0);
+        assert!(!accepted);
+        assert!(engine.log.read().unwrap().is_empty());
+    }
+
+    #[test]
+    fn truncates_conflicting_suffix_before_appending() {
+        let engine = make_engine();
+        // Leader replicates an initial run of entries at term 1.
+        engine.append_entries(1, "leader".to_string(), 0, 0, vec![entry(1), entry(1), entry(1)], 0);
+        assert_eq!(engine.log.read().unwrap().len(), 3);
+
+        // A new leader at term 2 overwrites entries 2 and 3 with its own.
+        let accepted = engine.append_entries(2, "leader2".to_string(), 1, 1, vec![entry(2), entry(2)], 3);
+
+        assert!(accepted);
+        let log = engine.log.read().unwrap();
+        assert_eq!(log.len(), 3);
+        assert_eq!(log[0].term, 1);
+        assert_eq!(log[1].term, 2);
+        assert_eq!(log[2].term, 2);
+        assert_eq!(engine.commit_index.load(Ordering::SeqCst), 3);
+    }
+}
@@ -1,9 +1,23 @@
 use std::collections::BTreeMap;
 use std::ffi::c_void;
+use std::io;
+use std::mem;
+use std::os::unix::io::RawFd;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::sandbox_jail::{
+    bpf_jump, bpf_stmt, sock_fprog, AUDIT_ARCH_X86_64, BPF_JMP_JEQ_K, BPF_LD_W_ABS, BPF_RET_K,
+    SECCOMP_DATA_ARCH_OFFSET, SECCOMP_DATA_NR_OFFSET,
+};
 
 pub type SyscallHandler = unsafe extern "C" fn(*mut c_void) -> i32;
 
+/// Registers captured from a `seccomp_notif`'s `seccomp_data.args`. x86_64
+/// only populates the argument registers (`rdi`..`r9`) plus `rip`; the
+/// other fields exist for API compatibility with earlier ptrace-based
+/// callers and are always zero here.
 #[repr(C)]
 pub struct RegisterContext {
     pub rax: u64,
@@ -26,6 +40,31 @@ pub struct RegisterContext {
     pub eflags: u64,
 }
 
+impl RegisterContext {
+    fn from_notif_args(args: &[u64; 6], instruction_pointer: u64) -> Self {
+        RegisterContext {
+            rax: 0,
+            rbx: 0,
+            rcx: 0,
+            rdx: args[2],
+            rsi: args[1],
+            rdi: args[0],
+            rbp: 0,
+            rsp: 0,
+            r8: args[4],
+            r9: args[5],
+            r10: args[3],
+            r11: 0,
+            r12: 0,
+            r13: 0,
+            r14: 0,
+            r15: 0,
+            rip: instruction_pointer,
+            eflags: 0,
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 pub enum HookType {
     PreExecution,
@@ -34,31 +73,156 @@ pub enum HookType {
     IORequest,
 }
 
+// uapi/linux/seccomp.h
+const SECCOMP_SET_MODE_FILTER: libc::c_uint = 1;
+const SECCOMP_FILTER_FLAG_NEW_LISTENER: libc::c_ulong = 1 << 3;
+const SECCOMP_RET_USER_NOTIF: u32 = 0x7fc0_0000;
+const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+const SECCOMP_USER_NOTIF_FLAG_CONTINUE: u32 = 1;
+
+// ioctl encoding for the SECCOMP_IOC_MAGIC ('!') command family, computed
+// the same way the kernel's _IOWR/_IOW macros do rather than hardcoding
+// the resulting numbers.
+const SECCOMP_IOC_MAGIC: u64 = '!' as u64;
+const IOC_NONE: u64 = 0;
+const IOC_WRITE: u64 = 1;
+const IOC_READ: u64 = 2;
+const IOC_READ_WRITE: u64 = IOC_READ | IOC_WRITE;
+
+const fn ioc(dir: u64, nr: u64, size: usize) -> u64 {
+    (dir << 30) | (SECCOMP_IOC_MAGIC << 8) | nr | ((size as u64) << 16)
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+struct SeccompData {
+    nr: i32,
+    arch: u32,
+    instruction_pointer: u64,
+    args: [u64; 6],
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+struct SeccompNotif {
+    id: u64,
+    pid: u32,
+    flags: u32,
+    data: SeccompData,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+struct SeccompNotifResp {
+    id: u64,
+    val: i64,
+    error: i32,
+    flags: u32,
+}
+
+fn notif_recv_ioctl() -> u64 {
+    ioc(IOC_READ_WRITE, 0, mem::size_of::<SeccompNotif>())
+}
+
+fn notif_send_ioctl() -> u64 {
+    ioc(IOC_READ_WRITE, 1, mem::size_of::<SeccompNotifResp>())
+}
+
+fn notif_id_valid_ioctl() -> u64 {
+    ioc(IOC_WRITE, 2, mem::size_of::<u64>())
+}
+
+/// Builds the filter installed with `SECCOMP_FILTER_FLAG_NEW_LISTENER`:
+/// syscalls in `syscall_ids` trap to the supervisor via
+/// `SECCOMP_RET_USER_NOTIF`, everything else is allowed outright. This is
+/// an interception filter, not a confinement one — `Jail` handles denying
+/// syscalls outright via its own allowlist filter.
+fn build_notify_program(syscall_ids: &[u32]) -> Result<Vec<crate::sandbox_jail::SockFilter>, &'static str> {
+    // The jt/jf jump fields below are single bytes, so the largest
+    // jump_to_notify we can ever encode is 255 — a watch-list of 256 or
+    // more syscalls would silently wrap and jump to the wrong instruction.
+    if syscall_ids.len() >= 256 {
+        return Err("syscall watch-list exceeds 255 entries, jump offsets cannot be encoded in a u8");
+    }
+
+    let mut program = Vec::with_capacity(syscall_ids.len() + 4);
+    program.push(bpf_stmt(BPF_LD_W_ABS, SECCOMP_DATA_ARCH_OFFSET));
+    program.push(bpf_jump(BPF_JMP_JEQ_K, AUDIT_ARCH_X86_64, 1, 0));
+    program.push(bpf_stmt(BPF_RET_K, SECCOMP_RET_ALLOW));
+    program.push(bpf_stmt(BPF_LD_W_ABS, SECCOMP_DATA_NR_OFFSET));
+
+    for (i, syscall_id) in syscall_ids.iter().enumerate() {
+        let jump_to_notify = (syscall_ids.len() - i) as u8;
+        program.push(bpf_jump(BPF_JMP_JEQ_K, *syscall_id, jump_to_notify, 0));
+    }
+
+    program.push(bpf_stmt(BPF_RET_K, SECCOMP_RET_ALLOW));
+    program.push(bpf_stmt(BPF_RET_K, SECCOMP_RET_USER_NOTIF));
+    Ok(program)
+}
+
 pub struct KernelInterceptor {
     active: AtomicBool,
-    syscall_table: BTreeMap<u32, SyscallHandler>,
+    syscall_table: Arc<Mutex<BTreeMap<u32, SyscallHandler>>>,
     hooked_functions: BTreeMap<String, usize>,
     safety_valve_enabled: bool,
+    notify_fd: Mutex<Option<RawFd>>,
 }
 
 impl KernelInterceptor {
     pub fn new() -> Self {
         KernelInterceptor {
             active: AtomicBool::new(false),
-            syscall_table: BTreeMap::new(),
+            syscall_table: Arc::new(Mutex::new(BTreeMap::new())),
             hooked_functions: BTreeMap::new(),
             safety_valve_enabled: true,
+            notify_fd: Mutex::new(None),
         }
     }
 
+    /// Installs the notification filter for the currently-registered
+    /// syscalls and spawns the supervisor thread that services it. Must be
+    /// called from the thread that will be confined — the filter applies
+    /// to the calling thread (and anything it execs), same as `Jail`'s
+    /// confinement filter.
     pub fn attach(&self) -> Result<(), &'static str> {
         if self.active.load(Ordering::SeqCst) {
             return Err("Interceptor already active");
         }
-        
-        // Simulation of hooking logic (e.g., using ptrace or kernel module interface)
-        // In a real scenario, this would involve unsafe pointer manipulation
+
+        let syscall_ids: Vec<u32> = self
+            .syscall_table
+            .lock()
+            .map_err(|_| "syscall table lock poisoned")?
+            .keys()
+            .copied()
+            .collect();
+        if syscall_ids.is_empty() {
+            return Err("no syscalls registered to intercept");
+        }
+
+        let program = build_notify_program(&syscall_ids)?;
+        let fprog = sock_fprog(&program);
+
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_seccomp,
+                SECCOMP_SET_MODE_FILTER,
+                SECCOMP_FILTER_FLAG_NEW_LISTENER,
+                &fprog as *const _ as *const c_void,
+            )
+        };
+        if fd < 0 {
+            return Err("seccomp(SECCOMP_SET_MODE_FILTER) failed");
+        }
+
+        *self.notify_fd.lock().map_err(|_| "notify fd lock poisoned")? = Some(fd as RawFd);
         self.active.store(true, Ordering::SeqCst);
+
+        let syscall_table = Arc::clone(&self.syscall_table);
+        let safety_valve_enabled = self.safety_valve_enabled;
+        thread::spawn(move || supervise(fd as RawFd, syscall_table, safety_valve_enabled));
+
         Ok(())
     }
 
@@ -67,11 +231,17 @@ impl KernelInterceptor {
             return Err("Interceptor not active");
         }
         self.active.store(false, Ordering::SeqCst);
+
+        if let Some(fd) = self.notify_fd.lock().map_err(|_| "notify fd lock poisoned")?.take() {
+            unsafe {
+                libc::close(fd);
+            }
+        }
         Ok(())
     }
 
     pub unsafe fn register_syscall_hook(&mut self, syscall_id: u32, handler: SyscallHandler) {
-        self.syscall_table.insert(syscall_id, handler);
+        self.syscall_table.lock().unwrap().insert(syscall_id, handler);
     }
 
     pub fn inspect_registers(&self, ctx: &RegisterContext) -> Vec<String> {
@@ -88,7 +258,7 @@ impl KernelInterceptor {
 
         let probe_id = self.hooked_functions.len() + 1;
         self.hooked_functions.insert(target_symbol.to_string(), probe_id);
-        
+
         match hook_type {
             HookType::PreExecution => println!("Injected PRE probe at {}", target_symbol),
             HookType::PostExecution => println!("Injected POST probe at {}", target_symbol),
@@ -115,8 +285,78 @@ impl KernelInterceptor {
     }
 }
 
-pub extern "C" fn generic_handler(ctx: *mut c_void) -> i32 {
-    // This function would handle the intercepted call
+/// Runs on its own thread for the lifetime of the notification fd: blocks
+/// in `SECCOMP_IOCTL_NOTIF_RECV`, dispatches the matching handler, and
+/// replies via `SECCOMP_IOCTL_NOTIF_SEND`. Exits once the fd is closed by
+/// `detach` (the `ioctl` then fails with `ENOENT`/`EBADF`).
+fn supervise(fd: RawFd, syscall_table: Arc<Mutex<BTreeMap<u32, SyscallHandler>>>, safety_valve_enabled: bool) {
+    loop {
+        let mut notif = SeccompNotif::default();
+        let recv = unsafe { libc::ioctl(fd, notif_recv_ioctl(), &mut notif as *mut SeccompNotif) };
+        if recv != 0 {
+            return;
+        }
+
+        let mut resp = SeccompNotifResp {
+            id: notif.id,
+            val: 0,
+            error: 0,
+            flags: SECCOMP_USER_NOTIF_FLAG_CONTINUE,
+        };
+
+        let handler = syscall_table.lock().ok().and_then(|t| t.get(&(notif.data.nr as u32)).copied());
+
+        if let Some(handler) = handler {
+            // TOCTOU guard: the target may have exited or exec'd since the
+            // notification was queued, which would make any pointer
+            // argument in `notif.data.args` refer to memory that's no
+            // longer what the registers described. Re-validate the
+            // notification id immediately before acting on it; if it's
+            // gone stale, skip straight to CONTINUE instead of running the
+            // handler against a target that may no longer exist.
+            let still_valid = unsafe {
+                libc::ioctl(fd, notif_id_valid_ioctl(), &notif.id as *const u64) == 0
+            };
+
+            if still_valid {
+                let mut ctx = RegisterContext::from_notif_args(&notif.data.args, notif.data.instruction_pointer);
+                let outcome = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| unsafe {
+                    handler(&mut ctx as *mut RegisterContext as *mut c_void)
+                }));
+
+                match outcome {
+                    Ok(ret) if ret >= 0 => {
+                        resp.val = ret as i64;
+                        resp.error = 0;
+                        resp.flags = 0;
+                    }
+                    Ok(ret) => {
+                        resp.val = -1;
+                        resp.error = -ret;
+                        resp.flags = 0;
+                    }
+                    Err(_) if safety_valve_enabled => {
+                        // A panicking handler must not wedge the target
+                        // thread forever; default to letting the syscall
+                        // proceed normally.
+                        resp.flags = SECCOMP_USER_NOTIF_FLAG_CONTINUE;
+                    }
+                    Err(_) => {
+                        resp.val = -1;
+                        resp.error = libc::EPERM;
+                        resp.flags = 0;
+                    }
+                }
+            }
+        }
+
+        unsafe {
+            libc::ioctl(fd, notif_send_ioctl(), &resp as *const SeccompNotifResp);
+        }
+    }
+}
+
+pub extern "C" fn generic_handler(_ctx: *mut c_void) -> i32 {
     0
 }
 
@@ -126,9 +366,17 @@ mod tests {
 
     #[test]
     fn test_interceptor_lifecycle() {
-        let interceptor = KernelInterceptor::new();
+        let mut interceptor = KernelInterceptor::new();
+        // No syscalls registered yet, so there's nothing to build a
+        // notification filter from.
+        assert!(interceptor.attach().is_err());
+
+        unsafe {
+            interceptor.register_syscall_hook(libc::SYS_getpid as u32, generic_handler);
+        }
         assert!(interceptor.attach().is_ok());
         assert!(interceptor.attach().is_err());
         assert!(interceptor.detach().is_ok());
+        assert!(interceptor.detach().is_err());
     }
 }
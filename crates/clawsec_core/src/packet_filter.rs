@@ -1,107 +1,136 @@
-use std::collections::HashSet;
-use std::net::{IpAddr, Ipv4Addr};
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
 use std::sync::{Arc, RwLock};
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use ipnetwork::IpNetwork;
+
 const BLOCKED_PORTS: [u16; 5] = [22, 23, 3389, 5900, 6667];
 const MAX_REQUESTS_PER_MINUTE: u32 = 60;
+const RATE_LIMIT_WINDOW_SECS: u64 = 60;
+
+/// Structured outcome of an inspection decision, replacing a bare `bool`
+/// (plus `println!`) so callers can tell a rate limit from a blocklist hit
+/// from an out-of-subnet source without parsing log lines.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InspectionVerdict {
+    Allowed,
+    BlockedPort(u16),
+    BlockedDomain(String),
+    BlockedSubnet(IpAddr),
+    BlockedSource(IpAddr),
+    RateLimited(IpAddr),
+}
+
+impl InspectionVerdict {
+    pub fn is_allowed(&self) -> bool {
+        matches!(self, InspectionVerdict::Allowed)
+    }
+}
 
 #[derive(Debug, Clone)]
 struct NetworkRule {
-    pub allowed_subnets: Vec<String>,
+    pub allowed_subnets: Vec<IpNetwork>,
     pub blocked_domains: HashSet<String>,
     pub rate_limit_window: u64,
 }
 
 pub struct PacketInspector {
     rules: Arc<RwLock<NetworkRule>>,
-    request_log: Arc<RwLock<Vec<u64>>>,
+    request_log: Arc<RwLock<HashMap<IpAddr, Vec<u64>>>>,
 }
 
 impl PacketInspector {
     pub fn new() -> Self {
         let default_rules = NetworkRule {
-            allowed_subnets: vec!["192.168.0.0/16".to_string(), "10.0.0.0/8".to_string()],
-            blocked_domains: ["malware.com", "crypto-miner.pool", "botnet.c2"]
+            allowed_subnets: ["192.168.0.0/16", "10.0.0.0/8"]
+                .iter()
+                .map(|s| s.parse().expect("static CIDR literal"))
+                .collect(),
+            blocked_domains: ["malware.com", "crypto-miner.pool", "botnet.c2", "*.botnet.c2"]
                 .iter()
                 .map(|&s| s.to_string())
                 .collect(),
-            rate_limit_window: 60,
+            rate_limit_window: RATE_LIMIT_WINDOW_SECS,
         };
 
         PacketInspector {
             rules: Arc::new(RwLock::new(default_rules)),
-            request_log: Arc::new(RwLock::new(Vec::new())),
+            request_log: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
-    pub fn inspect_outbound(&self, ip: IpAddr, port: u16, hostname: Option<&str>) -> bool {
-        // Step 1: Check blocked ports
+    pub fn inspect_outbound(&self, ip: IpAddr, port: u16, hostname: Option<&str>) -> InspectionVerdict {
         if BLOCKED_PORTS.contains(&port) {
-            println!("BLOCKED: Port {} is restricted by ClawSec policy.", port);
-            return false;
+            return InspectionVerdict::BlockedPort(port);
         }
 
-        // Step 2: Check blocked domains
         let rules = self.rules.read().unwrap();
         if let Some(host) = hostname {
-            if rules.blocked_domains.contains(host) {
-                println!("BLOCKED: Domain {} is in the blacklist.", host);
-                return false;
+            if let Some(pattern) = rules.blocked_domains.iter().find(|p| domain_matches(p, host)) {
+                return InspectionVerdict::BlockedDomain(pattern.clone());
             }
         }
 
-        // Step 3: Check IP Whitelist (Simplified CIDR logic)
         if !self.is_ip_allowed(ip, &rules.allowed_subnets) {
-            println!("BLOCKED: IP {} is outside allowed subnets.", ip);
-            return false;
+            return InspectionVerdict::BlockedSubnet(ip);
         }
 
-        // Step 4: Rate Limiting
-        if !self.check_rate_limit() {
-            println!("BLOCKED: Rate limit exceeded for outbound traffic.");
-            return false;
+        if !self.check_rate_limit(ip, rules.rate_limit_window) {
+            return InspectionVerdict::RateLimited(ip);
         }
 
-        true
+        InspectionVerdict::Allowed
     }
 
-    fn is_ip_allowed(&self, ip: IpAddr, subnets: &[String]) -> bool {
-        // Mock implementation of CIDR checking
-        // In production, use 'ipnetwork' crate
+    /// Authenticates a peer dialing into this node in a mesh topology:
+    /// the source must be a loopback address or fall inside one of
+    /// `allowed_subnets`, and is then subject to the same per-source rate
+    /// limiting as outbound traffic.
+    pub fn inspect_inbound(&self, source: IpAddr, port: u16) -> InspectionVerdict {
+        if BLOCKED_PORTS.contains(&port) {
+            return InspectionVerdict::BlockedPort(port);
+        }
+
+        let rules = self.rules.read().unwrap();
+        if !self.is_ip_allowed(source, &rules.allowed_subnets) {
+            return InspectionVerdict::BlockedSource(source);
+        }
+
+        if !self.check_rate_limit(source, rules.rate_limit_window) {
+            return InspectionVerdict::RateLimited(source);
+        }
+
+        InspectionVerdict::Allowed
+    }
+
+    fn is_ip_allowed(&self, ip: IpAddr, subnets: &[IpNetwork]) -> bool {
         match ip {
-            IpAddr::V4(ipv4) => {
-                if ipv4.is_private() || ipv4.is_loopback() {
-                    return true;
-                }
-                // Allow Google DNS for example
-                if ipv4 == Ipv4Addr::new(8, 8, 8, 8) {
-                    return true;
-                }
-            }
-            _ => return false,
+            IpAddr::V4(ipv4) if ipv4.is_loopback() => return true,
+            IpAddr::V6(ipv6) if ipv6.is_loopback() => return true,
+            _ => {}
         }
-        
-        // Default deny for public internet unless explicitly whitelisted
-        false 
+
+        subnets.iter().any(|subnet| subnet.contains(ip))
     }
 
-    fn check_rate_limit(&self) -> bool {
+    /// Sliding-window rate limit keyed per source IP, rather than one
+    /// global counter shared by every peer.
+    fn check_rate_limit(&self, source: IpAddr, window_secs: u64) -> bool {
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_secs();
 
         let mut log = self.request_log.write().unwrap();
-        
-        // Prune old logs
-        log.retain(|&timestamp| timestamp > now - 60);
+        let entries = log.entry(source).or_default();
+        entries.retain(|&timestamp| timestamp + window_secs > now);
 
-        if log.len() as u32 >= MAX_REQUESTS_PER_MINUTE {
+        if entries.len() as u32 >= MAX_REQUESTS_PER_MINUTE {
             return false;
         }
 
-        log.push(now);
+        entries.push(now);
         true
     }
 
@@ -114,6 +143,16 @@ impl PacketInspector {
     }
 }
 
+/// Matches `host` against a blocklist `pattern`, supporting exact matches
+/// and `*.suffix` wildcards (e.g. `*.botnet.c2` matches `relay.botnet.c2`
+/// but not `botnet.c2` itself).
+fn domain_matches(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(suffix) => host.ends_with(suffix) && host.len() > suffix.len() && host.as_bytes()[host.len() - suffix.len() - 1] == b'.',
+        None => pattern == host,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -121,16 +160,62 @@ mod tests {
     #[test]
     fn test_blocked_ports() {
         let inspector = PacketInspector::new();
-        assert_eq!(inspector.inspect_outbound("127.0.0.1".parse().unwrap(), 22, None), false);
-        assert_eq!(inspector.inspect_outbound("127.0.0.1".parse().unwrap(), 80, None), true);
+        assert_eq!(inspector.inspect_outbound("127.0.0.1".parse().unwrap(), 22, None), InspectionVerdict::BlockedPort(22));
+        assert_eq!(inspector.inspect_outbound("127.0.0.1".parse().unwrap(), 80, None), InspectionVerdict::Allowed);
     }
 
     #[test]
     fn test_rate_limiting() {
         let inspector = PacketInspector::new();
+        let ip: IpAddr = "127.0.0.1".parse().unwrap();
         for _ in 0..MAX_REQUESTS_PER_MINUTE {
-            assert_eq!(inspector.check_rate_limit(), true);
+            assert!(inspector.check_rate_limit(ip, RATE_LIMIT_WINDOW_SECS));
         }
-        assert_eq!(inspector.check_rate_limit(), false);
+        assert!(!inspector.check_rate_limit(ip, RATE_LIMIT_WINDOW_SECS));
+    }
+
+    #[test]
+    fn test_rate_limit_is_per_source() {
+        let inspector = PacketInspector::new();
+        let a: IpAddr = "127.0.0.1".parse().unwrap();
+        let b: IpAddr = "127.0.0.2".parse().unwrap();
+        for _ in 0..MAX_REQUESTS_PER_MINUTE {
+            assert!(inspector.check_rate_limit(a, RATE_LIMIT_WINDOW_SECS));
+        }
+        assert!(!inspector.check_rate_limit(a, RATE_LIMIT_WINDOW_SECS));
+        assert!(inspector.check_rate_limit(b, RATE_LIMIT_WINDOW_SECS));
+    }
+
+    #[test]
+    fn test_cidr_matching() {
+        let inspector = PacketInspector::new();
+        assert_eq!(inspector.inspect_outbound("10.1.2.3".parse().unwrap(), 80, None), InspectionVerdict::Allowed);
+        assert_eq!(
+            inspector.inspect_outbound("8.8.8.8".parse().unwrap(), 80, None),
+            InspectionVerdict::BlockedSubnet("8.8.8.8".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_wildcard_domain_blocking() {
+        let inspector = PacketInspector::new();
+        assert_eq!(
+            inspector.inspect_outbound("10.0.0.1".parse().unwrap(), 80, Some("relay.botnet.c2")),
+            InspectionVerdict::BlockedDomain("*.botnet.c2".to_string())
+        );
+        assert_eq!(
+            inspector.inspect_outbound("10.0.0.1".parse().unwrap(), 80, Some("botnet.c2")),
+            InspectionVerdict::BlockedDomain("botnet.c2".to_string())
+        );
+    }
+
+    #[test]
+    fn test_inbound_rejects_outside_subnets() {
+        let inspector = PacketInspector::new();
+        assert_eq!(
+            inspector.inspect_inbound("8.8.8.8".parse().unwrap(), 443),
+            InspectionVerdict::BlockedSource("8.8.8.8".parse().unwrap())
+        );
+        assert_eq!(inspector.inspect_inbound("10.1.2.3".parse().unwrap(), 443), InspectionVerdict::Allowed);
     }
 }
@@ -0,0 +1,188 @@
+use std::path::Path;
+use std::sync::Mutex;
+
+use rocksdb::{IteratorMode, Options, WriteBatch, DB};
+
+use crate::distributed_consensus::LogEntry;
+
+const CF_LOG: &str = "raft_log";
+const CF_META: &str = "raft_meta";
+const HARD_STATE_KEY: &[u8] = b"hard_state";
+
+/// Durable backing store for Raft hard state and log entries.
+///
+/// Implementors must guarantee that `persist_hard_state` and `append` are
+/// fsync'd to disk before returning, since the consensus engine relies on
+/// this to uphold the "never vote twice in the same term" safety property
+/// across restarts.
+pub trait RaftStorage: Send + Sync {
+    fn persist_hard_state(&self, term: u64, voted_for: Option<&str>) -> std::io::Result<()>;
+    fn load_hard_state(&self) -> std::io::Result<(u64, Option<String>)>;
+    fn append(&self, entries: &[LogEntry]) -> std::io::Result<()>;
+    fn entries(&self, from: u64, to: u64) -> std::io::Result<Vec<LogEntry>>;
+    fn truncate_suffix(&self, index: u64) -> std::io::Result<()>;
+    fn last_index(&self) -> std::io::Result<u64>;
+}
+
+/// RocksDB-backed implementation of `RaftStorage`.
+///
+/// Log entries are keyed by their big-endian index in the `raft_log` column
+/// family so range scans come back in order. Hard state (current term and
+/// vote) lives under a single key in `raft_meta` so a term/vote update is
+/// one atomic write.
+pub struct RocksRaftStorage {
+    db: DB,
+    next_index: Mutex<u64>,
+}
+
+impl RocksRaftStorage {
+    pub fn open(path: &Path) -> std::io::Result<Self> {
+        let mut opts = Options::default();
+        opts.create_if_missing(true);
+        opts.create_missing_column_families(true);
+
+        let db = DB::open_cf(&opts, path, [CF_LOG, CF_META])
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+
+        let next_index = Self::scan_last_index(&db)? + 1;
+        Ok(RocksRaftStorage {
+            db,
+            next_index: Mutex::new(next_index),
+        })
+    }
+
+    fn scan_last_index(db: &DB) -> std::io::Result<u64> {
+        let cf = db.cf_handle(CF_LOG).expect("raft_log column family");
+        let mut iter = db.iterator_cf(cf, IteratorMode::End);
+        match iter.next() {
+            Some(Ok((key, _))) => Ok(index_from_key(&key)),
+            _ => Ok(0),
+        }
+    }
+
+    fn log_cf(&self) -> &rocksdb::ColumnFamily {
+        self.db.cf_handle(CF_LOG).expect("raft_log column family")
+    }
+
+    fn meta_cf(&self) -> &rocksdb::ColumnFamily {
+        self.db.cf_handle(CF_META).expect("raft_meta column family")
+    }
+}
+
+fn key_for_index(index: u64) -> [u8; 8] {
+    index.to_be_bytes()
+}
+
+fn index_from_key(key: &[u8]) -> u64 {
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(key);
+    u64::from_be_bytes(buf)
+}
+
+impl RaftStorage for RocksRaftStorage {
+    fn persist_hard_state(&self, term: u64, voted_for: Option<&str>) -> std::io::Result<()> {
+        let mut buf = Vec::with_capacity(9 + voted_for.map_or(0, str::len));
+        buf.extend_from_slice(&term.to_be_bytes());
+        match voted_for {
+            Some(candidate) => {
+                buf.push(1);
+                buf.extend_from_slice(candidate.as_bytes());
+            }
+            None => buf.push(0),
+        }
+
+        self.db
+            .put_cf(self.meta_cf(), HARD_STATE_KEY, &buf)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+        self.db
+            .flush_wal(true)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))
+    }
+
+    fn load_hard_state(&self) -> std::io::Result<(u64, Option<String>)> {
+        let raw = self
+            .db
+            .get_cf(self.meta_cf(), HARD_STATE_KEY)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+
+        let Some(raw) = raw else {
+            return Ok((0, None));
+        };
+
+        let mut term_bytes = [0u8; 8];
+        term_bytes.copy_from_slice(&raw[0..8]);
+        let term = u64::from_be_bytes(term_bytes);
+
+        let voted_for = if raw[8] == 1 {
+            Some(String::from_utf8_lossy(&raw[9..]).into_owned())
+        } else {
+            None
+        };
+
+        Ok((term, voted_for))
+    }
+
+    fn append(&self, entries: &[LogEntry]) -> std::io::Result<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        let mut next_index = self.next_index.lock().unwrap();
+        let mut batch = WriteBatch::default();
+        for entry in entries {
+            let encoded =
+                bincode::serialize(entry).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+            batch.put_cf(self.log_cf(), key_for_index(*next_index), encoded);
+            *next_index += 1;
+        }
+
+        self.db
+            .write(batch)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+        self.db
+            .flush_wal(true)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))
+    }
+
+    fn entries(&self, from: u64, to: u64) -> std::io::Result<Vec<LogEntry>> {
+        if from == 0 || to < from {
+            return Ok(Vec::new());
+        }
+
+        let mut out = Vec::with_capacity((to - from + 1) as usize);
+        let iter = self
+            .db
+            .iterator_cf(self.log_cf(), IteratorMode::From(&key_for_index(from), rocksdb::Direction::Forward));
+
+        for item in iter {
+            let (key, value) = item.map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+            let index = index_from_key(&key);
+            if index > to {
+                break;
+            }
+            let entry: LogEntry = bincode::deserialize(&value)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+            out.push(entry);
+        }
+
+        Ok(out)
+    }
+
+    fn truncate_suffix(&self, index: u64) -> std::io::Result<()> {
+        let mut next_index = self.next_index.lock().unwrap();
+        if index >= *next_index {
+            return Ok(());
+        }
+
+        self.db
+            .delete_range_cf(self.log_cf(), key_for_index(index), key_for_index(*next_index))
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+        *next_index = index;
+        Ok(())
+    }
+
+    fn last_index(&self) -> std::io::Result<u64> {
+        let next_index = self.next_index.lock().unwrap();
+        Ok(next_index.saturating_sub(1))
+    }
+}
@@ -0,0 +1,195 @@
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::distributed_consensus::LogEntry;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(500);
+const IO_TIMEOUT: Duration = Duration::from_millis(500);
+const MAX_FRAME_BYTES: u32 = 16 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppendEntriesRequest {
+    pub term: u64,
+    pub leader_id: String,
+    pub prev_log_index: u64,
+    pub prev_log_term: u64,
+    pub entries: Vec<LogEntry>,
+    pub leader_commit: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppendEntriesResponse {
+    pub term: u64,
+    pub success: bool,
+    pub match_index: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequestVoteRequest {
+    pub term: u64,
+    pub candidate_id: String,
+    pub last_log_index: u64,
+    pub last_log_term: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequestVoteResponse {
+    pub term: u64,
+    pub vote_granted: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RaftRpc {
+    AppendEntries(AppendEntriesRequest),
+    RequestVote(RequestVoteRequest),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RaftRpcResponse {
+    AppendEntries(AppendEntriesResponse),
+    RequestVote(RequestVoteResponse),
+}
+
+/// Pluggable wire layer for the two Raft RPCs, so the consensus engine
+/// doesn't have to know whether peers are dialed over TCP, a Unix socket,
+/// or an in-process channel during tests.
+pub trait Transport: Send + Sync {
+    fn send_append_entries(
+        &self,
+        address: &str,
+        req: AppendEntriesRequest,
+    ) -> io::Result<AppendEntriesResponse>;
+
+    fn send_request_vote(
+        &self,
+        address: &str,
+        req: RequestVoteRequest,
+    ) -> io::Result<RequestVoteResponse>;
+}
+
+/// Length-prefixed, bincode-framed TCP transport: a 4-byte big-endian length
+/// header followed by the serialized payload, on both the request and the
+/// response.
+pub struct TcpTransport {
+    retry_attempts: u32,
+    retry_backoff: Duration,
+}
+
+impl TcpTransport {
+    pub fn new(retry_attempts: u32, retry_backoff: Duration) -> Self {
+        TcpTransport {
+            retry_attempts,
+            retry_backoff,
+        }
+    }
+
+    fn call(&self, address: &str, rpc: RaftRpc) -> io::Result<RaftRpcResponse> {
+        let mut last_err = None;
+        for attempt in 0..=self.retry_attempts {
+            match Self::call_once(address, &rpc) {
+                Ok(resp) => return Ok(resp),
+                Err(e) => {
+                    last_err = Some(e);
+                    if attempt < self.retry_attempts {
+                        std::thread::sleep(self.retry_backoff * (attempt + 1));
+                    }
+                }
+            }
+        }
+        Err(last_err.unwrap_or_else(|| io::Error::new(io::ErrorKind::Other, "unreachable peer")))
+    }
+
+    fn call_once(address: &str, rpc: &RaftRpc) -> io::Result<RaftRpcResponse> {
+        let addr = address
+            .parse()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, format!("bad peer address: {address}")))?;
+        let mut stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)?;
+        stream.set_read_timeout(Some(IO_TIMEOUT))?;
+        stream.set_write_timeout(Some(IO_TIMEOUT))?;
+        stream.set_nodelay(true)?;
+
+        write_frame(&mut stream, rpc)?;
+        read_frame(&mut stream)
+    }
+}
+
+impl Transport for TcpTransport {
+    fn send_append_entries(
+        &self,
+        address: &str,
+        req: AppendEntriesRequest,
+    ) -> io::Result<AppendEntriesResponse> {
+        match self.call(address, RaftRpc::AppendEntries(req))? {
+            RaftRpcResponse::AppendEntries(resp) => Ok(resp),
+            RaftRpcResponse::RequestVote(_) => {
+                Err(io::Error::new(io::ErrorKind::InvalidData, "mismatched RPC response"))
+            }
+        }
+    }
+
+    fn send_request_vote(
+        &self,
+        address: &str,
+        req: RequestVoteRequest,
+    ) -> io::Result<RequestVoteResponse> {
+        match self.call(address, RaftRpc::RequestVote(req))? {
+            RaftRpcResponse::RequestVote(resp) => Ok(resp),
+            RaftRpcResponse::AppendEntries(_) => {
+                Err(io::Error::new(io::ErrorKind::InvalidData, "mismatched RPC response"))
+            }
+        }
+    }
+}
+
+/// Accepts inbound RPC connections and hands each decoded request to
+/// `handler`, writing back whatever response it returns. Runs on the calling
+/// thread; `ConsensusEngine::start` runs it on a thread of its own, separate
+/// from the election/heartbeat loop.
+pub fn serve(listener: TcpListener, handler: impl Fn(RaftRpc) -> RaftRpcResponse + Send + Sync + 'static) -> io::Result<()> {
+    for stream in listener.incoming() {
+        let mut stream = stream?;
+        stream.set_read_timeout(Some(IO_TIMEOUT))?;
+        stream.set_write_timeout(Some(IO_TIMEOUT))?;
+
+        let rpc: RaftRpc = match read_frame(&mut stream) {
+            Ok(rpc) => rpc,
+            Err(e) => {
+                eprintln!("failed to read raft rpc frame: {}", e);
+                continue;
+            }
+        };
+
+        let response = handler(rpc);
+        if let Err(e) = write_frame(&mut stream, response) {
+            eprintln!("failed to write raft rpc response: {}", e);
+        }
+    }
+    Ok(())
+}
+
+fn write_frame<T: Serialize>(stream: &mut TcpStream, value: T) -> io::Result<()> {
+    let payload =
+        bincode::serialize(&value).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+    if payload.len() as u64 > MAX_FRAME_BYTES as u64 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "raft rpc frame too large"));
+    }
+    stream.write_all(&(payload.len() as u32).to_be_bytes())?;
+    stream.write_all(&payload)?;
+    stream.flush()
+}
+
+fn read_frame<T: for<'de> Deserialize<'de>>(stream: &mut TcpStream) -> io::Result<T> {
+    let mut len_buf = [0u8; 4];
+    stream.read_exact(&mut len_buf)?;
+    let len = u32::from_be_bytes(len_buf);
+    if len > MAX_FRAME_BYTES {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "raft rpc frame too large"));
+    }
+
+    let mut buf = vec![0u8; len as usize];
+    stream.read_exact(&mut buf)?;
+    bincode::deserialize(&buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+}
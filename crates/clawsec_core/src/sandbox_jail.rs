@@ -12,6 +12,32 @@ struct CloneArgs {
     stack_top: *mut u8,
 }
 
+/// What happens to a syscall that isn't on the allowlist.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SeccompDefaultAction {
+    /// Fails the syscall with the given errno (e.g. `EPERM`) instead of
+    /// killing the process — `SCMP_ACT_ERRNO(errno)`.
+    Errno(i32),
+    /// `SCMP_ACT_KILL`: the process is terminated immediately.
+    Kill,
+}
+
+/// Syscall numbers (x86_64) that `allow_net` injects automatically so
+/// network access is enforced at the kernel level rather than only by the
+/// namespace/cgroup layer.
+const NET_SYSCALLS: &[i64] = &[
+    41,  // socket
+    42,  // connect
+    43,  // accept
+    44,  // sendto
+    45,  // recvfrom
+    49,  // bind
+    50,  // listen
+    51,  // getsockname
+    54,  // setsockopt
+    288, // accept4
+];
+
 pub struct JailConfig {
     pub root_dir: PathBuf,
     pub hostname: String,
@@ -19,6 +45,30 @@ pub struct JailConfig {
     pub allow_net: bool,
     pub uid: u32,
     pub gid: u32,
+    /// Syscall numbers (x86_64) the confined process may issue. `allow_net`
+    /// extends this list with the socket-family syscalls at `enter()` time.
+    pub allowed_syscalls: Vec<i64>,
+    pub seccomp_default_action: SeccompDefaultAction,
+    /// Cap on `pids.max` (v2) / `pids.max` under the pids controller (v1),
+    /// limiting fork-bomb-style task growth inside the jail.
+    pub pids_limit: u64,
+    /// When set, `enter` also unshares `CLONE_NEWUSER` and writes
+    /// `uid_map`/`gid_map` before dropping privileges, letting an
+    /// unprivileged caller run a confined command instead of requiring
+    /// root to start with.
+    pub rootless: bool,
+    pub uid_map: Vec<IdMapping>,
+    pub gid_map: Vec<IdMapping>,
+}
+
+/// One line of a `/proc/[pid]/{uid,gid}_map` entry: `count` consecutive ids
+/// starting at `inside_id` map to the ids starting at `outside_id` in the
+/// parent namespace.
+#[derive(Debug, Clone, Copy)]
+pub struct IdMapping {
+    pub inside_id: u32,
+    pub outside_id: u32,
+    pub count: u32,
 }
 
 impl Default for JailConfig {
@@ -30,10 +80,157 @@ impl Default for JailConfig {
             allow_net: false,
             uid: 65534,
             gid: 65534,
+            allowed_syscalls: default_syscall_allowlist(),
+            seccomp_default_action: SeccompDefaultAction::Errno(libc::EPERM),
+            pids_limit: 64,
+            rootless: false,
+            uid_map: Vec::new(),
+            gid_map: Vec::new(),
         }
     }
 }
 
+impl JailConfig {
+    /// Maps container uid/gid 0 onto the given real uid/gid — the identity
+    /// mapping rootless container runtimes default to. `uid`/`gid` must be
+    /// captured *before* `unshare(CLONE_NEWUSER)` runs: once inside the new
+    /// (still-unmapped) user namespace, `getuid`/`getgid` return the
+    /// overflow id (65534) rather than the invoking user's real one, per
+    /// `user_namespaces(7)`.
+    pub fn identity_rootless_maps(uid: u32, gid: u32) -> (Vec<IdMapping>, Vec<IdMapping>) {
+        (
+            vec![IdMapping { inside_id: 0, outside_id: uid, count: 1 }],
+            vec![IdMapping { inside_id: 0, outside_id: gid, count: 1 }],
+        )
+    }
+}
+
+/// A minimal set of syscalls needed to load and exec a dynamically linked
+/// binary, read/write its stdio, and exit cleanly. Callers extend this via
+/// `JailConfig::allowed_syscalls` for anything beyond that baseline.
+fn default_syscall_allowlist() -> Vec<i64> {
+    vec![
+        0,   // read
+        1,   // write
+        2,   // open
+        3,   // close
+        5,   // fstat
+        9,   // mmap
+        10,  // mprotect
+        11,  // munmap
+        12,  // brk
+        21,  // access
+        59,  // execve
+        60,  // exit
+        63,  // uname
+        158, // arch_prctl
+        231, // exit_group
+        257, // openat
+        273, // set_robust_list
+        302, // prlimit64
+        318, // getrandom
+    ]
+}
+
+const PR_SET_NO_NEW_PRIVS: libc::c_int = 38;
+const PR_SET_SECCOMP: libc::c_int = 22;
+const SECCOMP_MODE_FILTER: libc::c_ulong = 2;
+
+pub(crate) const AUDIT_ARCH_X86_64: u32 = 0xC000_003E;
+pub(crate) const SECCOMP_DATA_ARCH_OFFSET: u32 = 4;
+pub(crate) const SECCOMP_DATA_NR_OFFSET: u32 = 0;
+
+const SECCOMP_RET_KILL: u32 = 0x0000_0000;
+const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+
+pub(crate) const BPF_LD_W_ABS: u16 = 0x00 | 0x00 | 0x20; // BPF_LD | BPF_W | BPF_ABS
+pub(crate) const BPF_JMP_JEQ_K: u16 = 0x05 | 0x10 | 0x00; // BPF_JMP | BPF_JEQ | BPF_K
+pub(crate) const BPF_RET_K: u16 = 0x06 | 0x00; // BPF_RET | BPF_K
+
+const BPF_MAXINSNS: usize = 4096;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct SockFilter {
+    code: u16,
+    jt: u8,
+    jf: u8,
+    k: u32,
+}
+
+#[repr(C)]
+pub(crate) struct SockFprog {
+    len: u16,
+    filter: *const SockFilter,
+}
+
+pub(crate) fn bpf_stmt(code: u16, k: u32) -> SockFilter {
+    SockFilter { code, jt: 0, jf: 0, k }
+}
+
+pub(crate) fn bpf_jump(code: u16, k: u32, jt: u8, jf: u8) -> SockFilter {
+    SockFilter { code, jt, jf, k }
+}
+
+pub(crate) fn sock_fprog(program: &[SockFilter]) -> SockFprog {
+    SockFprog {
+        len: program.len() as u16,
+        filter: program.as_ptr(),
+    }
+}
+
+/// Builds the classic BPF program installed via `SECCOMP_MODE_FILTER`:
+/// reject anything not compiled for the expected architecture, then allow
+/// only the syscalls in `allowed`, falling through to `default_action` for
+/// everything else.
+fn build_seccomp_program(
+    allowed: &[i64],
+    default_action: SeccompDefaultAction,
+) -> io::Result<Vec<SockFilter>> {
+    if allowed.len() + 4 > BPF_MAXINSNS {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "seccomp filter too long"));
+    }
+    // The jt/jf jump fields below are single bytes, so the largest
+    // jump_to_allow we can ever encode is 255 — an allowlist of 256 or more
+    // entries would silently wrap and jump to the wrong instruction.
+    if allowed.len() >= 256 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "seccomp allowlist exceeds 255 entries, jump offsets cannot be encoded in a u8",
+        ));
+    }
+
+    let mut program = Vec::with_capacity(allowed.len() + 4);
+    program.push(bpf_stmt(BPF_LD_W_ABS, SECCOMP_DATA_ARCH_OFFSET));
+    program.push(bpf_jump(BPF_JMP_JEQ_K, AUDIT_ARCH_X86_64, 1, 0));
+    program.push(bpf_stmt(BPF_RET_K, SECCOMP_RET_KILL));
+    program.push(bpf_stmt(BPF_LD_W_ABS, SECCOMP_DATA_NR_OFFSET));
+
+    for (i, syscall) in allowed.iter().enumerate() {
+        let jump_to_allow = (allowed.len() - i) as u8; // skip remaining checks + the default-action RET
+        program.push(bpf_jump(BPF_JMP_JEQ_K, *syscall as u32, jump_to_allow, 0));
+    }
+
+    let default_ret = match default_action {
+        SeccompDefaultAction::Kill => SECCOMP_RET_KILL,
+        SeccompDefaultAction::Errno(errno) => SECCOMP_RET_ERRNO | (errno as u32 & 0xFFFF),
+    };
+    program.push(bpf_stmt(BPF_RET_K, default_ret));
+    program.push(bpf_stmt(BPF_RET_K, SECCOMP_RET_ALLOW));
+
+    Ok(program)
+}
+
+/// Renders mapping entries as the newline-separated `inside outside count`
+/// lines the kernel expects in `uid_map`/`gid_map`.
+fn format_id_map(mappings: &[IdMapping]) -> String {
+    mappings
+        .iter()
+        .map(|m| format!("{} {} {}\n", m.inside_id, m.outside_id, m.count))
+        .collect()
+}
+
 pub struct Jail {
     config: JailConfig,
     active_pid: Option<i32>,
@@ -47,20 +244,108 @@ impl Jail {
         }
     }
 
+    /// Installs the classic-BPF seccomp filter built from
+    /// `config.allowed_syscalls` (plus the socket-family syscalls when
+    /// `allow_net` is set) and `config.seccomp_default_action`. Must run
+    /// after `drop_privileges` and before the jailed command execs, since
+    /// the filter is inherited across `fork`/`exec` but not retroactively
+    /// applied to syscalls already in flight.
+    fn install_seccomp_filter(&self) -> io::Result<()> {
+        let mut allowed = self.config.allowed_syscalls.clone();
+        if self.config.allow_net {
+            allowed.extend_from_slice(NET_SYSCALLS);
+        }
+
+        let program = build_seccomp_program(&allowed, self.config.seccomp_default_action)?;
+
+        unsafe {
+            if libc::prctl(PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let fprog = sock_fprog(&program);
+
+            if libc::prctl(
+                PR_SET_SECCOMP,
+                SECCOMP_MODE_FILTER,
+                &fprog as *const SockFprog as libc::c_ulong,
+                0,
+                0,
+            ) != 0
+            {
+                // EINVAL here most commonly means the running kernel doesn't
+                // support SECCOMP_MODE_FILTER or the BPF program itself was
+                // rejected by the verifier (e.g. an out-of-range jump).
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn prepare_filesystem(&self) -> io::Result<()> {
         if !self.config.root_dir.exists() {
             fs::create_dir_all(&self.config.root_dir)?;
         }
-        
+
         let proc_path = self.config.root_dir.join("proc");
         if !proc_path.exists() {
             fs::create_dir(&proc_path)?;
         }
 
+        // Mountpoint `pivot_root` puts the old root at; removed again once
+        // `enter` has detached it.
+        let oldroot_path = self.config.root_dir.join("oldroot");
+        if !oldroot_path.exists() {
+            fs::create_dir(&oldroot_path)?;
+        }
+
         Ok(())
     }
 
+    /// Confines `pid` to a memory/pids-limited cgroup, preferring the
+    /// unified (v2) hierarchy and falling back to v1 when the host hasn't
+    /// migrated — cgroup v1 silently no-ops on unified-only systems, so we
+    /// detect which one is live rather than assuming v1 unconditionally.
     pub fn apply_cgroups(&self, pid: i32) -> io::Result<()> {
+        if Path::new("/sys/fs/cgroup/cgroup.controllers").exists() {
+            self.apply_cgroups_v2(pid)
+        } else {
+            self.apply_cgroups_v1(pid)
+        }
+    }
+
+    fn apply_cgroups_v2(&self, pid: i32) -> io::Result<()> {
+        let root = Path::new("/sys/fs/cgroup");
+        let scope_path = root.join("clawsec.scope");
+        if !scope_path.exists() {
+            fs::create_dir_all(&scope_path)?;
+        }
+
+        // Controllers must be enabled in the parent's subtree_control before
+        // the child scope can see them in its own cgroup.controllers.
+        let mut f_subtree = File::create(root.join("cgroup.subtree_control"))?;
+        f_subtree.write_all(b"+memory +pids")?;
+
+        let limit_bytes = self.config.memory_limit_mb * 1024 * 1024;
+        let mut f_max = File::create(scope_path.join("memory.max"))?;
+        f_max.write_all(limit_bytes.to_string().as_bytes())?;
+
+        // Soft throttling point before the hard limit kicks in.
+        let high_bytes = limit_bytes.saturating_mul(9) / 10;
+        let mut f_high = File::create(scope_path.join("memory.high"))?;
+        f_high.write_all(high_bytes.to_string().as_bytes())?;
+
+        let mut f_pids = File::create(scope_path.join("pids.max"))?;
+        f_pids.write_all(self.config.pids_limit.to_string().as_bytes())?;
+
+        let mut f_procs = File::create(scope_path.join("cgroup.procs"))?;
+        f_procs.write_all(pid.to_string().as_bytes())?;
+
+        Ok(())
+    }
+
+    fn apply_cgroups_v1(&self, pid: i32) -> io::Result<()> {
         let cgroup_path = Path::new("/sys/fs/cgroup/memory/clawsec");
         if !cgroup_path.exists() {
             fs::create_dir_all(cgroup_path)?;
@@ -73,6 +358,15 @@ impl Jail {
         let mut f_tasks = File::create(cgroup_path.join("tasks"))?;
         f_tasks.write_all(pid.to_string().as_bytes())?;
 
+        let pids_path = Path::new("/sys/fs/cgroup/pids/clawsec");
+        if !pids_path.exists() {
+            fs::create_dir_all(pids_path)?;
+        }
+        let mut f_pids_max = File::create(pids_path.join("pids.max"))?;
+        f_pids_max.write_all(self.config.pids_limit.to_string().as_bytes())?;
+        let mut f_pids_tasks = File::create(pids_path.join("tasks"))?;
+        f_pids_tasks.write_all(pid.to_string().as_bytes())?;
+
         Ok(())
     }
 
@@ -80,22 +374,33 @@ impl Jail {
         let root_c = CString::new(self.config.root_dir.to_str().unwrap()).unwrap();
         let hostname_c = CString::new(self.config.hostname.clone()).unwrap();
 
-        if libc::unshare(libc::CLONE_NEWNS | libc::CLONE_NEWUTS | libc::CLONE_NEWPID) != 0 {
-            return Err(io::Error::last_os_error());
-        }
+        if self.config.rootless {
+            // Must be captured before unshare(CLONE_NEWUSER): inside the
+            // new, still-unmapped user namespace, getuid()/getgid() report
+            // the overflow id instead of the invoking user's real one.
+            let real_uid = libc::getuid();
+            let real_gid = libc::getgid();
 
-        if libc::sethostname(hostname_c.as_ptr() as *const i8, self.config.hostname.len()) != 0 {
-            return Err(io::Error::last_os_error());
+            // The user namespace must exist before uid_map/gid_map can be
+            // written, and must be created before the other namespaces so
+            // the capabilities it grants inside the new namespace cover the
+            // mounts/pivot_root/hostname changes that follow.
+            if libc::unshare(libc::CLONE_NEWUSER) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            self.write_id_maps(real_uid, real_gid)?;
         }
 
-        if libc::chroot(root_c.as_ptr()) != 0 {
+        if libc::unshare(libc::CLONE_NEWNS | libc::CLONE_NEWUTS | libc::CLONE_NEWPID) != 0 {
             return Err(io::Error::last_os_error());
         }
 
-        if libc::chdir(b"/\0".as_ptr() as *const i8) != 0 {
+        if libc::sethostname(hostname_c.as_ptr() as *const i8, self.config.hostname.len()) != 0 {
             return Err(io::Error::last_os_error());
         }
 
+        self.pivot_into_root(&root_c)?;
+
         if libc::mount(
             b"proc\0".as_ptr() as *const i8,
             b"/proc\0".as_ptr() as *const i8,
@@ -107,6 +412,94 @@ impl Jail {
         }
 
         self.drop_privileges()?;
+        self.install_seccomp_filter()?;
+
+        Ok(())
+    }
+
+    /// Writes `/proc/self/{setgroups,uid_map,gid_map}` for the user
+    /// namespace just unshared. `setgroups` must be set to `deny` before
+    /// `gid_map` is writable by an unprivileged caller, and the kernel only
+    /// accepts a single write to each map file — any retry after a short
+    /// write or formatting mistake fails the jail rather than partially
+    /// applying.
+    fn write_id_maps(&self, real_uid: u32, real_gid: u32) -> io::Result<()> {
+        let mut f_setgroups = File::create("/proc/self/setgroups")?;
+        f_setgroups.write_all(b"deny")?;
+
+        let uid_map = if self.config.uid_map.is_empty() {
+            let (uid, _) = JailConfig::identity_rootless_maps(real_uid, real_gid);
+            uid
+        } else {
+            self.config.uid_map.clone()
+        };
+        let gid_map = if self.config.gid_map.is_empty() {
+            let (_, gid) = JailConfig::identity_rootless_maps(real_uid, real_gid);
+            gid
+        } else {
+            self.config.gid_map.clone()
+        };
+
+        let mut f_uid_map = File::create("/proc/self/uid_map")?;
+        f_uid_map.write_all(format_id_map(&uid_map).as_bytes())?;
+
+        let mut f_gid_map = File::create("/proc/self/gid_map")?;
+        f_gid_map.write_all(format_id_map(&gid_map).as_bytes())?;
+
+        Ok(())
+    }
+
+    /// Confines the process to `root_dir` via `pivot_root` rather than
+    /// `chroot`. Unlike `chroot`, this actually changes the process's root
+    /// mount, so a leftover fd to the old root or a second `chroot` call
+    /// can't be used to climb back out — the classic chroot escape.
+    unsafe fn pivot_into_root(&self, root_c: &CString) -> io::Result<()> {
+        // Bind-mount root_dir onto itself so it's a mount point in its own
+        // right (pivot_root requires new_root to be a mount point), then
+        // mark it MS_PRIVATE|MS_REC so mount/unmount events inside the jail
+        // never propagate back out to the host.
+        if libc::mount(
+            root_c.as_ptr(),
+            root_c.as_ptr(),
+            ptr::null(),
+            libc::MS_BIND | libc::MS_REC,
+            ptr::null(),
+        ) != 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+
+        if libc::mount(
+            ptr::null(),
+            root_c.as_ptr(),
+            ptr::null(),
+            libc::MS_PRIVATE | libc::MS_REC,
+            ptr::null(),
+        ) != 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+
+        if libc::chdir(root_c.as_ptr()) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let oldroot_rel = CString::new("oldroot").unwrap();
+        if libc::syscall(libc::SYS_pivot_root, root_c.as_ptr(), oldroot_rel.as_ptr()) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        if libc::chdir(b"/\0".as_ptr() as *const i8) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        if libc::umount2(b"/oldroot\0".as_ptr() as *const i8, libc::MNT_DETACH) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        if libc::rmdir(b"/oldroot\0".as_ptr() as *const i8) != 0 {
+            return Err(io::Error::last_os_error());
+        }
 
         Ok(())
     }